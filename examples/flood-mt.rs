@@ -48,9 +48,9 @@ fn main() {
         .enumerate()
     {
         let sock = if idx == 0 {
-            Socket::with_shared(&info, &umem).unwrap()
+            Socket::with_shared(info, &umem).unwrap()
         } else {
-            Socket::new(&info).unwrap()
+            Socket::new(info).unwrap()
         };
 
         if idx == dev_idx {
@@ -71,9 +71,9 @@ fn main() {
         if idx == dev_idx {
             eprintln!("Binding socket {idx} {}", rxtx.as_raw_fd());
             // Ready to bind, i.e. kernel to start doing things on the ring.
-            umem.bind(&rxtx).unwrap();
+            umem.bind(rxtx).unwrap();
         } else {
-            queue.bind(&rxtx).unwrap();
+            queue.bind(rxtx).unwrap();
         }
     }
 
@@ -2,23 +2,16 @@
 //!
 //! This will very aggressively poll the queues. Seriously, wasting time is an understatement. Do
 //! not use in production and aim at a network interface with care!
-use core::{mem::MaybeUninit, num::NonZeroU32, ptr::NonNull};
+use core::num::NonZeroU32;
 use xdpilone::xdp::XdpDesc;
 use xdpilone::{BufIdx, IfInfo, Socket, SocketConfig, Umem, UmemConfig};
 
-// We can use _any_ data mapping, so let's use a static one setup by the linker/loader.
-#[repr(align(4096))]
-struct PacketMap(MaybeUninit<[u8; 1 << 20]>);
-
 fn main() {
     let args = <Args as clap::Parser>::parse();
 
-    let alloc = Box::new(PacketMap(MaybeUninit::uninit()));
-    // Register the packet buffer with the kernel, getting an XDP socket file descriptor for it.
-    let mem = NonNull::new(Box::leak(alloc).0.as_mut_ptr()).unwrap();
-
-    // Safety: we guarantee this mapping is aligned, and will be alive. It is static, after-all.
-    let umem = unsafe { Umem::new(UmemConfig::default(), mem) }.unwrap();
+    // Allocate a packet buffer and register it with the kernel, getting an XDP socket file
+    // descriptor for it. The library owns the memory and keeps it alive for all our queues.
+    let umem = Umem::allocate(UmemConfig::default(), 1 << 8).unwrap();
     let info = ifinfo(&args).unwrap();
 
     // Let's use that same file descriptor for our packet buffer operations on the specified
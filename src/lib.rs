@@ -14,14 +14,22 @@
 #![deny(missing_docs)]
 extern crate alloc;
 
+/// Owned memory mappings, for a `Umem` allocated by the library.
+mod mmap;
 /// User-space side of one or multiple XDP sockets.
 mod xsk;
 
+pub use mmap::PageSize;
 pub use xsk::{
-    BufIdx, DeviceQueue, IfInfo, ReadComplete, ReadRx, RingCons, RingProd, RingRx, RingTx, Socket,
-    SocketConfig, Umem, UmemChunk, UmemConfig, User, WriteFill, WriteTx,
+    BorrowedUmem, Bound, BufIdx, ConfigError, ConfigField, DeviceQueue, FillManager, FramePool,
+    FrameSidecar, FrameState, FrameStateError, IfInfo, ReadComplete, ReadRx, RingCons, RingProd,
+    RingRx, RingState, RingTx, RxFrame, RxPacket, SetupError, SharedTx, Socket, SocketConfig, Umem,
+    UmemChunk, UmemConfig, UmemPartition, User, WakeOutcome, WriteFill, WriteTx,
 };
 
+#[cfg(feature = "sim")]
+pub use xsk::Simulator;
+
 /// Bindings for XDP kernel-interface, including structs.
 pub mod xdp;
 
@@ -0,0 +1,296 @@
+use core::ptr::NonNull;
+
+use crate::xsk::ptr_len;
+use crate::{Errno, LastErrno};
+
+/// An owned memory mapping, unmapped when dropped.
+///
+/// This is the backing store of a [`crate::Umem`] that was allocated by the library itself. All
+/// objects that are derived from such a `Umem` hold a reference counted pointer to it, so that
+/// the memory stays valid for as long as the kernel or the user may refer to it through any of
+/// the rings.
+pub(crate) struct Mmap {
+    addr: NonNull<[u8]>,
+    page_size: PageSize,
+    /// The memory file backing a shared mapping, closed on drop.
+    memfd: Option<libc::c_int>,
+    /// The NUMA node the memory is bound to, if any.
+    node: Option<u32>,
+}
+
+/// The size of pages backing the memory area of a [`crate::Umem`].
+///
+/// See [`crate::Umem::allocate_with_pages`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PageSize {
+    /// The system's base page size, usually 4 KiB.
+    Base,
+    /// Huge pages of 2 MiB.
+    Huge2MiB,
+    /// Huge pages of 1 GiB.
+    Huge1GiB,
+}
+
+impl PageSize {
+    /// Not defined in all libc versions, part of the Linux ABI. See `<linux/mman.h>`.
+    const MAP_HUGE_SHIFT: libc::c_int = 26;
+
+    /// The number of bytes in one page of this size.
+    pub fn bytes(self) -> usize {
+        match self {
+            PageSize::Base => unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize },
+            PageSize::Huge2MiB => 1 << 21,
+            PageSize::Huge1GiB => 1 << 30,
+        }
+    }
+
+    /// The `mmap` flags requesting pages of this size.
+    fn mmap_flags(self) -> libc::c_int {
+        match self {
+            PageSize::Base => 0,
+            PageSize::Huge2MiB => libc::MAP_HUGETLB | (21 << Self::MAP_HUGE_SHIFT),
+            PageSize::Huge1GiB => libc::MAP_HUGETLB | (30 << Self::MAP_HUGE_SHIFT),
+        }
+    }
+}
+
+impl Mmap {
+    /// Memory policy modes and flags, see `<linux/mempolicy.h>`. Not defined by libc.
+    const MPOL_BIND: libc::c_int = 2;
+    const MPOL_MF_STRICT: libc::c_uint = 1 << 0;
+
+    /// Map a private, anonymous region of at least `len` bytes.
+    pub(crate) fn anonymous(len: usize) -> Result<Self, Errno> {
+        Self::anonymous_with_pages(len, PageSize::Base)
+    }
+
+    /// Map a private, anonymous region of at least `len` bytes backed by pages of a given size.
+    ///
+    /// Huge pages must have been reserved by the system administrator, otherwise this fails. The
+    /// length is rounded up to a multiple of the page size.
+    pub(crate) fn anonymous_with_pages(len: usize, page_size: PageSize) -> Result<Self, Errno> {
+        if len == 0 {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        let page = page_size.bytes();
+        let len = len
+            .checked_add(page - 1)
+            .map(|len| len & !(page - 1))
+            .ok_or(Errno(libc::EINVAL))?;
+
+        let mmap = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | page_size.mmap_flags(),
+                -1,
+                0,
+            )
+        };
+
+        if mmap == libc::MAP_FAILED {
+            return Err(LastErrno)?;
+        }
+
+        assert!(!mmap.is_null());
+        // Safety: as by MMap this pointer is valid.
+        let addr = core::ptr::slice_from_raw_parts_mut(mmap as *mut u8, len);
+        let addr = unsafe { NonNull::new_unchecked(addr) };
+
+        Ok(Mmap {
+            addr,
+            page_size,
+            memfd: None,
+            node: None,
+        })
+    }
+
+    /// The seals of a memory file, which fix its size for all processes mapping it.
+    const MEMFD_SEALS: libc::c_int = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW;
+
+    /// Create a new memory file of `len` bytes, and map it shared.
+    ///
+    /// The file can be mapped by other processes, see [`Mmap::attach_memfd`]. Its size is sealed,
+    /// so that no process can shrink the file under the mapping of another.
+    pub(crate) fn create_memfd(len: usize) -> Result<Self, Errno> {
+        if len == 0 {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        let name = b"xdpilone-umem\0";
+        // Not all libc versions define the wrapper, use the system call directly.
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_memfd_create,
+                name.as_ptr() as *const libc::c_char,
+                libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+            )
+        };
+
+        if fd < 0 {
+            return Err(LastErrno)?;
+        }
+
+        let fd = fd as libc::c_int;
+        let len_off = match libc::off_t::try_from(len) {
+            Ok(len) => len,
+            Err(_) => {
+                unsafe { libc::close(fd) };
+                return Err(Errno(libc::EINVAL));
+            }
+        };
+
+        if unsafe { libc::ftruncate(fd, len_off) } != 0 {
+            let err = Errno::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(err);
+        }
+
+        if unsafe { libc::fcntl(fd, libc::F_ADD_SEALS, Self::MEMFD_SEALS) } != 0 {
+            let err = Errno::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(err);
+        }
+
+        Self::shared(fd, len)
+    }
+
+    /// Map an existing memory file shared, as created by [`Mmap::create_memfd`].
+    ///
+    /// The file descriptor is duplicated, the caller retains ownership of `fd`. Returns the
+    /// mapping of the whole file. Fails with `EINVAL` unless the size of the file is sealed, as
+    /// otherwise another process could shrink it and accesses to the mapping would fault.
+    pub(crate) fn attach_memfd(fd: libc::c_int) -> Result<Self, Errno> {
+        let seals = unsafe { libc::fcntl(fd, libc::F_GET_SEALS) };
+        if seals < 0 {
+            return Err(LastErrno)?;
+        }
+
+        if seals & Self::MEMFD_SEALS != Self::MEMFD_SEALS {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        let mut stat = core::mem::MaybeUninit::<libc::stat>::uninit();
+        if unsafe { libc::fstat(fd, stat.as_mut_ptr()) } != 0 {
+            return Err(LastErrno)?;
+        }
+
+        // Safety: initialized by the successful `fstat` call.
+        let stat = unsafe { stat.assume_init() };
+        let len = usize::try_from(stat.st_size).map_err(|_| Errno(libc::EINVAL))?;
+
+        if len == 0 {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        let fd = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(LastErrno)?;
+        }
+
+        Self::shared(fd, len)
+    }
+
+    /// Map `len` bytes of a file shared, taking ownership of the file descriptor.
+    fn shared(fd: libc::c_int, len: usize) -> Result<Self, Errno> {
+        let mmap = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            )
+        };
+
+        if mmap == libc::MAP_FAILED {
+            let err = Errno::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(err);
+        }
+
+        assert!(!mmap.is_null());
+        // Safety: as by MMap this pointer is valid.
+        let addr = core::ptr::slice_from_raw_parts_mut(mmap as *mut u8, len);
+        let addr = unsafe { NonNull::new_unchecked(addr) };
+
+        Ok(Mmap {
+            addr,
+            page_size: PageSize::Base,
+            memfd: Some(fd),
+            node: None,
+        })
+    }
+
+    /// Bind the memory of the region to a NUMA node, with `mbind`.
+    ///
+    /// This must be called before any page of the region has been touched, i.e. before the area
+    /// is registered with the kernel, as pages already faulted in are not moved.
+    pub(crate) fn bind_node(&mut self, node: u32) -> Result<(), Errno> {
+        const BITS: u32 = libc::c_ulong::BITS;
+
+        let mut nodemask = alloc::vec![0 as libc::c_ulong; (node / BITS) as usize + 1];
+        nodemask[(node / BITS) as usize] |= 1 << (node % BITS);
+        // The kernel expects one more than the number of bits it should read from the mask.
+        let maxnode = nodemask.len() as libc::c_ulong * libc::c_ulong::from(BITS) + 1;
+
+        let err = unsafe {
+            libc::syscall(
+                libc::SYS_mbind,
+                self.addr.as_ptr() as *mut u8,
+                ptr_len(self.addr.as_ptr()),
+                Self::MPOL_BIND,
+                nodemask.as_ptr(),
+                maxnode,
+                Self::MPOL_MF_STRICT,
+            )
+        };
+
+        if err != 0 {
+            return Err(LastErrno)?;
+        }
+
+        self.node = Some(node);
+        Ok(())
+    }
+
+    /// The complete mapped region.
+    pub(crate) fn as_ptr(&self) -> NonNull<[u8]> {
+        self.addr
+    }
+
+    /// The size of pages backing this region.
+    pub(crate) fn page_size(&self) -> PageSize {
+        self.page_size
+    }
+
+    /// The NUMA node this region is bound to, if any.
+    pub(crate) fn node(&self) -> Option<u32> {
+        self.node
+    }
+
+    /// The memory file backing this region, if any.
+    pub(crate) fn memfd(&self) -> Option<libc::c_int> {
+        self.memfd
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        let len = ptr_len(self.addr.as_ptr());
+        unsafe { libc::munmap(self.addr.as_ptr() as *mut _, len) };
+
+        if let Some(fd) = self.memfd {
+            let _ = unsafe { libc::close(fd) };
+        }
+    }
+}
+
+// Safety: the mapping is not tied to any thread. Access to the contents is the responsibility of
+// the owner of the derived pointers, same as for any `Umem` area.
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
@@ -15,6 +15,17 @@ pub struct XdpDesc {
     pub options: u32,
 }
 
+impl XdpDesc {
+    /// Option-bit: the packet continues in the next descriptor.
+    ///
+    /// Used for multi-buffer packets, see `SocketConfig::XDP_USE_SG`.
+    pub const XDP_PKT_CONTD: u32 = 1 << 0;
+    /// Option-bit: the kernel should read a [`XskTxMetadata`] request in front of the data.
+    ///
+    /// Requires the Umem to be configured with a `tx_metadata_len`.
+    pub const XDP_TX_METADATA: u32 = 1 << 1;
+}
+
 /// Argument to `setsockopt(_, SOL_XDP, XDP_UMEM_REG)`.
 ///
 /// Note that this struct's size determines the kernel interpretation of the option. In particular,
@@ -148,3 +159,74 @@ impl Default for SockAddrXdp {
         }
     }
 }
+
+/// Metadata for a transmitted frame, placed immediately in front of the packet data.
+///
+/// The kernel reads this request from `addr - tx_metadata_len` of a descriptor that has the
+/// [`XdpDesc::XDP_TX_METADATA`] option set. On completion it may write back the `completion`
+/// variant, such as a hardware timestamp, to the same location.
+#[repr(C)]
+#[doc(alias = "xsk_tx_metadata")]
+#[derive(Default, Debug, Copy, Clone)]
+pub struct XskTxMetadata {
+    /// A bitfield of `XDP_TXMD_FLAGS_*`, selecting the requested offloads.
+    pub flags: u64,
+    pub data: XskTxMetadataData,
+}
+
+/// The request or completion part of [`XskTxMetadata`].
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union XskTxMetadataData {
+    pub request: XskTxMetadataRequest,
+    pub completion: XskTxMetadataCompletion,
+}
+
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone)]
+pub struct XskTxMetadataRequest {
+    /// Offset, from the start of the packet, where checksumming starts.
+    pub csum_start: u16,
+    /// Offset, from `csum_start`, where the checksum is stored.
+    pub csum_offset: u16,
+    /// Only used on >= Linux 6.14
+    pub launch_time: u64,
+}
+
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone)]
+pub struct XskTxMetadataCompletion {
+    pub tx_timestamp: u64,
+}
+
+impl XskTxMetadata {
+    /// Request a hardware timestamp, reported in the completion.
+    pub const XDP_TXMD_FLAGS_TIMESTAMP: u64 = 1 << 0;
+    /// Request the device to compute and insert a checksum.
+    pub const XDP_TXMD_FLAGS_CHECKSUM: u64 = 1 << 1;
+    /// Request transmission at the given launch time.
+    pub const XDP_TXMD_FLAGS_LAUNCH_TIME: u64 = 1 << 2;
+
+    /// Create a metadata request, with no offloads selected.
+    pub fn request(request: XskTxMetadataRequest) -> Self {
+        XskTxMetadata {
+            flags: 0,
+            data: XskTxMetadataData { request },
+        }
+    }
+}
+
+impl Default for XskTxMetadataData {
+    fn default() -> Self {
+        XskTxMetadataData {
+            request: XskTxMetadataRequest::default(),
+        }
+    }
+}
+
+impl core::fmt::Debug for XskTxMetadataData {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Which variant is valid depends on the direction, we can not know.
+        f.debug_struct("XskTxMetadataData").finish_non_exhaustive()
+    }
+}
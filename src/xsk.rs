@@ -7,30 +7,59 @@
 //! The data structures here are not *safe* to construct. Some of them depend on the caller to
 //! uphold guarantees such as keeping an mmap alive, or holding onto a socket for them. Take care.
 
+/// Implementations for a `Umem` with a borrowed area.
+mod borrowed;
+/// Implementations for keeping the fill ring topped up.
+mod fill;
 /// Implementations for interface related operations.
 mod iface;
+/// Implementations for disjoint ranges of frames.
+mod partition;
+/// Implementations for the frame allocator.
+mod pool;
 /// Implementations for primitives `XskRing`, `RingProd`, `RingCons`.
 mod ring;
+/// Implementations for a transmit ring shared between threads.
+mod shared;
+/// Implementations for per-frame user data.
+mod sidecar;
+/// Implementations for an in-memory simulation of the kernel side of rings.
+#[cfg(feature = "sim")]
+mod sim;
 /// Implementations for sockets.
 mod socket;
 /// Implementation for memory management.
 mod umem;
 /// Implementations for the actual queue management (user-space side).
 mod user;
+/// Implementations for checking configurations before they are passed to the kernel.
+mod validate;
 
+use crate::mmap::Mmap;
 use crate::xdp::XdpMmapOffsets;
 
 use alloc::sync::Arc;
-use core::sync::atomic::AtomicU32;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, AtomicU32};
 use core::{num::NonZeroU32, ptr::NonNull};
 
-pub(crate) struct SocketFd(libc::c_int);
+pub(crate) struct SocketFd(libc::c_int, MappedRings);
+
+/// Which of the rings of a socket are currently mapped by a [`RingRx`] or [`RingTx`].
+///
+/// Each ring has exactly one user space side, a second handle would race the first.
+#[derive(Default)]
+pub(crate) struct MappedRings {
+    rx: AtomicBool,
+    tx: AtomicBool,
+}
 
 /// Not defined in all libc versions and a _system_ property, not an implementation property. Thus
 /// we define it ourselves here.
 pub(crate) const SOL_XDP: libc::c_int = 283;
 
-pub use self::user::{ReadComplete, ReadRx, WriteFill, WriteTx};
+pub use self::user::{ReadComplete, ReadRx, RxFrame, RxPacket, WriteFill, WriteTx};
 
 /// Internal structure shared for all rings.
 ///
@@ -57,6 +86,9 @@ struct XskRing {
     ring: NonNull<core::ffi::c_void>,
     /// The mmaped-consumer flags base.
     flags: NonNull<u32>,
+    /// Entries reserved or peeked by us, but not yet submitted, released or cancelled.
+    #[cfg(feature = "ring-invariants")]
+    outstanding: u32,
 }
 
 /// Static configuration describing a memory area to use for ring chunks.
@@ -71,7 +103,16 @@ pub struct UmemConfig {
     /// Reserved area at the start of the kernel area.
     pub headroom: u32,
     /// Flags to set with the creation calls.
+    ///
+    /// See [`UmemConfig::XDP_UMEM_UNALIGNED_CHUNK_FLAG`].
     pub flags: u32,
+    /// Size of the TX metadata area in front of each transmitted packet.
+    ///
+    /// Must be a multiple of 8 and less than 256. When non-zero the library also sets
+    /// [`UmemConfig::XDP_UMEM_TX_METADATA_LEN`] in the flags. To use
+    /// [`WriteTx::insert_with_metadata`] this must be at least the size of
+    /// [`crate::xdp::XskTxMetadata`].
+    pub tx_metadata_len: u32,
 }
 
 /// Configuration for a created socket.
@@ -111,6 +152,36 @@ pub struct Umem {
     config: UmemConfig,
     fd: Arc<SocketFd>,
     devices: DeviceControl,
+    /// The owner of the area, if it was allocated by us.
+    area: Option<Arc<Mmap>>,
+    /// If the area is borrowed by a `BorrowedUmem`, all derived objects must be bound.
+    borrowed: bool,
+}
+
+/// A [`Umem`] using a borrowed buffer as its memory area.
+///
+/// This is an alternative to [`Umem::new`] with a narrower contract. All sockets, queues and rings
+/// that are derived from it are wrapped in [`Bound`], which carries the lifetime of the borrow.
+/// Hence, the buffer can not be dropped or used otherwise while any of them is alive. The one
+/// remaining obligation of the caller is to not leak any of them, see [`BorrowedUmem::new`].
+///
+/// The wrapper dereferences to the `Umem` for access to its frames. However, the methods of the
+/// `Umem` and of the bound objects that derive further sockets, queues or rings refuse to do so
+/// for a borrowed area. Use the respective methods of this wrapper and of [`Bound`] instead.
+// Implementation: <xsk/borrowed.rs>
+pub struct BorrowedUmem<'area> {
+    umem: Umem,
+    area: PhantomData<&'area mut [u8]>,
+}
+
+/// A socket, queue or ring derived from a [`BorrowedUmem`].
+///
+/// This dereferences to the inner value but ensures it does not outlive the borrowed buffer. There
+/// is no mutable access to the inner value, which could be swapped out of the wrapper. Instead,
+/// the methods that need exclusive access are forwarded by the wrapper itself.
+pub struct Bound<'area, T> {
+    inner: T,
+    area: PhantomData<&'area ()>,
 }
 
 /// A raw pointer to a specific chunk in a Umem.
@@ -127,6 +198,164 @@ pub struct UmemChunk {
     pub offset: u64,
 }
 
+/// An exclusive range of the frames of a [`Umem`].
+///
+/// Created by [`Umem::partition`] or [`Umem::partition_ranges`]. The frames of the partitions
+/// returned by one such call are disjoint, and a partition only produces indices and chunks of its
+/// own frames. Each partition can be sent to a separate thread, to fill and recycle frames of one
+/// queue without sharing any state with the others, for instance with
+/// [`FramePool::with_partition`].
+///
+/// As with [`Umem::frame`], the chunks are raw pointers. The partition does not stop the frames
+/// from being used through the `Umem` itself or by another call to partition it. It can not be
+/// cloned, such that one call hands out each of its frames to exactly one owner.
+// Implementation: <xsk/partition.rs>
+pub struct UmemPartition {
+    umem_area: NonNull<[u8]>,
+    config: UmemConfig,
+    /// The first frame of this partition.
+    start: u32,
+    /// One past the last frame of this partition.
+    end: u32,
+    /// Keeps an owned `Umem` area alive, never read.
+    #[allow(dead_code)]
+    area: Option<Arc<Mmap>>,
+}
+
+/// An allocator of frames in a [`Umem`], tracking the state of each frame.
+///
+/// The pool hands out frames by their [`BufIdx`] and checks that each frame is passed through the
+/// rings in a consistent manner. A frame starts out [`FrameState::Free`], is put into the fill ring
+/// or taken by the user, returned by the receive ring, queued for and committed to transmission,
+/// and finally returned to the pool from the completion ring. Using a frame in a state that does
+/// not permit an operation results in a [`FrameStateError`] instead of silently handing the same
+/// memory to the kernel twice.
+///
+/// This is optional. The rings themselves do not know about the pool, all of its checks rely on
+/// the pool being used consistently for all frames of its `Umem`.
+// Implementation: <xsk/pool.rs>
+pub struct FramePool {
+    umem_area: NonNull<[u8]>,
+    config: UmemConfig,
+    /// The index of the first frame managed by the pool.
+    first: u32,
+    /// The state of each frame, indexed by the frame index relative to `first`.
+    states: Vec<FrameState>,
+    /// All frames currently in the `Free` state.
+    free: Vec<BufIdx>,
+    /// All frames currently in the `Transmit` state.
+    queued: Vec<BufIdx>,
+    /// Keeps an owned `Umem` area alive, never read.
+    #[allow(dead_code)]
+    area: Option<Arc<Mmap>>,
+}
+
+/// Keeps the fill ring of a [`DeviceQueue`] between a low and a high watermark.
+///
+/// Call [`FillManager::refill`] regularly, for instance after each batch of received packets. Once
+/// no more than the low watermark of entries are left in the fill ring for the kernel, the ring is
+/// topped up to the high watermark with frames from a source of the caller. The manager also
+/// reports, through [`FillManager::starvation`], when the kernel found the fill ring empty and
+/// had to drop packets.
+// Implementation: <xsk/fill.rs>
+#[derive(Debug, Clone)]
+pub struct FillManager {
+    low: u32,
+    high: u32,
+    /// The last observed value of `rx_fill_ring_empty_descs`.
+    empty_descs: Option<u64>,
+}
+
+/// User data for each frame of a [`Umem`], stored outside the memory shared with the kernel.
+///
+/// The entries are indexed by the same [`BufIdx`] as [`Umem::frame`]. They can also be looked up
+/// by the descriptors of the receive ring and the addresses of the completion ring, so that state
+/// such as a flow identifier or a timestamp follows a frame through the rings.
+// Implementation: <xsk/sidecar.rs>
+pub struct FrameSidecar<T> {
+    config: UmemConfig,
+    /// The index of the first frame with an entry.
+    first: u32,
+    /// The entries, indexed by the frame index relative to `first`.
+    entries: Vec<T>,
+}
+
+/// The state of a frame in a [`FramePool`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FrameState {
+    /// Available for allocation in the pool.
+    Free,
+    /// Owned by the user after being allocated, for instance to prepare a packet for transmission.
+    Allocated,
+    /// Given to the kernel in the fill ring.
+    Fill,
+    /// Returned by the kernel in the receive ring, now owned by the user.
+    Received,
+    /// Inserted into a transmit writer, not yet committed to the kernel.
+    Transmit,
+    /// Committed to the transmit ring, awaiting completion by the kernel.
+    Sent,
+}
+
+/// An operation on a frame in a [`FramePool`] that was not valid in the frame's current state.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameStateError {
+    frame: BufIdx,
+    state: Option<FrameState>,
+}
+
+/// A configuration value that the kernel is known to reject.
+///
+/// Returned by [`UmemConfig::validate`] and [`SocketConfig::validate`]. Methods that pass a
+/// configuration to the kernel run the same checks before any system call and fail with a
+/// [`SetupError::Config`].
+// Implementation: <xsk/validate.rs>
+#[derive(Clone, Copy, Debug)]
+pub struct ConfigError {
+    field: ConfigField,
+    reason: &'static str,
+}
+
+/// An error while creating a [`Umem`] or configuring the rings of a socket.
+///
+/// Distinguishes a configuration that was rejected before any system call, which names the field
+/// that is wrong, from an error reported by the system.
+// Implementation: <xsk/validate.rs>
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SetupError {
+    /// The configuration is invalid, nothing was created.
+    Config(ConfigError),
+    /// A system call failed.
+    Os(crate::Errno),
+}
+
+/// A field of [`UmemConfig`] or [`SocketConfig`], or the memory area of a [`Umem`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ConfigField {
+    /// [`UmemConfig::fill_size`].
+    FillSize,
+    /// [`UmemConfig::complete_size`].
+    CompleteSize,
+    /// [`UmemConfig::frame_size`].
+    FrameSize,
+    /// [`UmemConfig::headroom`].
+    Headroom,
+    /// [`UmemConfig::flags`].
+    Flags,
+    /// [`UmemConfig::tx_metadata_len`].
+    TxMetadataLen,
+    /// The length of the memory area registered for a `Umem`.
+    Area,
+    /// [`SocketConfig::rx_size`].
+    RxSize,
+    /// [`SocketConfig::tx_size`].
+    TxSize,
+    /// [`SocketConfig::bind_flags`].
+    BindFlags,
+}
+
 #[derive(Clone)]
 struct DeviceControl {
     /// The tracker, not critical for memory safety (here anyways) but correctness.
@@ -136,7 +365,6 @@ struct DeviceControl {
 /// A synchronized set for tracking which `IfCtx` are taken.
 trait ControlSet: Send + Sync + 'static {
     fn insert(&self, _: IfCtx) -> bool;
-    fn contains(&self, _: &IfCtx) -> bool;
     fn remove(&self, _: &IfCtx);
 }
 
@@ -146,6 +374,10 @@ trait ControlSet: Send + Sync + 'static {
 pub struct Socket {
     info: Arc<IfInfo>,
     fd: Arc<SocketFd>,
+    /// Keeps an owned `Umem` area alive, if this socket was derived from one.
+    area: Option<Arc<Mmap>>,
+    /// If this socket was derived from a `BorrowedUmem`, all derived objects must be bound.
+    borrowed: bool,
 }
 
 /// One device queue associated with an XDP socket.
@@ -183,6 +415,9 @@ pub struct User {
 pub struct RingRx {
     ring: RingCons,
     fd: Arc<SocketFd>,
+    /// Keeps an owned `Umem` area alive, never read.
+    #[allow(dead_code)]
+    area: Option<Arc<Mmap>>,
 }
 
 /// A transmitter queue.
@@ -192,6 +427,67 @@ pub struct RingRx {
 pub struct RingTx {
     ring: RingProd,
     fd: Arc<SocketFd>,
+    /// Keeps an owned `Umem` area alive, never read.
+    #[allow(dead_code)]
+    area: Option<Arc<Mmap>>,
+}
+
+/// A transmit ring that several threads write to concurrently.
+///
+/// Created by [`RingTx::share`], or by the simulation of the rings, for the duration of a borrow.
+/// Each call to [`SharedTx::insert_slice`] claims a range of slots with an atomic operation, copies
+/// the descriptors and then publishes them to the kernel. Ranges are published in the order they
+/// were claimed, so a thread may briefly spin until all threads with earlier ranges have published
+/// theirs.
+// Implementation: <xsk/shared.rs>
+pub struct SharedTx<'ring> {
+    ring: &'ring mut RingProd,
+    /// The producer head including all claimed, possibly not yet published, slots.
+    claimed: AtomicU32,
+    /// The socket to wake, none for a simulated ring.
+    fd: Option<&'ring SocketFd>,
+}
+
+/// An in-memory simulation of the four rings of a socket, including the kernel side.
+///
+/// The rings are regular [`RingProd`] and [`RingCons`] over memory that is not shared with the
+/// kernel, so this requires neither privileges nor a network device. The user side is accessed
+/// through the same readers and writers as a [`DeviceQueue`], [`RingRx`] and [`RingTx`]. The
+/// kernel side is driven explicitly, by consuming fill and transmit entries and producing
+/// completions and receive descriptors, or all at once with [`Simulator::loopback`].
+///
+/// This is meant for testing code that operates on the rings. It does not know about a `Umem`
+/// and never accesses the frames. Only available with the `sim` feature.
+// Implementation: <xsk/sim.rs>
+#[cfg(feature = "sim")]
+pub struct Simulator {
+    fill: RingProd,
+    fill_kernel: XskRing,
+    comp: RingCons,
+    comp_kernel: XskRing,
+    rx: RingCons,
+    rx_kernel: XskRing,
+    tx: RingProd,
+    tx_kernel: XskRing,
+}
+
+/// The result of a wakeup call that the kernel did not reject outright.
+///
+/// Returned by [`RingTx::try_wake`], [`RingRx::try_wake`] and [`DeviceQueue::try_wake`]. Errors
+/// that persist, such as `ENETDOWN` when the interface is down or `ENXIO` when the socket is not
+/// bound, are returned as an `Err` instead.
+// Implementation: <xsk/user.rs>
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum WakeOutcome {
+    /// The wakeup was delivered.
+    Woken,
+    /// The kernel could not process the wakeup at the moment, call it again later.
+    ///
+    /// This is one of `EAGAIN`, `EBUSY` or `EINTR`. For instance, the driver may still be busy with
+    /// a previous wakeup, or the transmit queue of the device is full. Note that `ENOBUFS` is
+    /// returned as an error, it indicates a socket without the ring that was to be woken.
+    Retry(crate::Errno),
 }
 
 /// A complete (cached) information about a socket.
@@ -267,6 +563,41 @@ pub struct RingCons {
     mmap_addr: NonNull<[u8]>,
 }
 
+/// A snapshot of the state of a ring, for debugging.
+///
+/// Returned by the `state` methods of [`RingProd`], [`RingCons`], [`RingRx`] and [`RingTx`], and
+/// by [`DeviceQueue::fill_state`] and [`DeviceQueue::complete_state`]. The heads shared with the
+/// kernel are loaded one after another, the snapshot is not atomic. Its `Debug` output explains
+/// the values.
+// Implementation: <xsk/ring.rs>
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct RingState {
+    /// The producer head shared with the kernel.
+    pub producer: u32,
+    /// The consumer head shared with the kernel.
+    pub consumer: u32,
+    /// Our cached version of the producer head.
+    ///
+    /// If we produce, this is ahead of `producer` by the entries reserved but not yet submitted.
+    /// Otherwise, it is the producer head as last loaded.
+    pub cached_producer: u32,
+    /// Our cached version of the consumer head.
+    ///
+    /// If we consume, this is ahead of `consumer` by the entries peeked but not yet released.
+    /// Otherwise, it is the consumer head as last loaded. Once refreshed, it is kept one ring
+    /// size ahead, see [`RingProd::count_free`].
+    pub cached_consumer: u32,
+    /// The number of entries in the ring.
+    pub size: u32,
+    /// The mask of entry indices to ring slots.
+    pub mask: u32,
+    /// The flags set by the kernel, such as `XDP_RING_NEED_WAKEUP`.
+    pub flags: u32,
+    /// If user space is the producer of the ring, otherwise the kernel is.
+    pub user_produces: bool,
+}
+
 impl Default for UmemConfig {
     fn default() -> Self {
         UmemConfig {
@@ -275,6 +606,7 @@ impl Default for UmemConfig {
             frame_size: 1 << 12,
             headroom: 0,
             flags: 0,
+            tx_metadata_len: 0,
         }
     }
 }
@@ -289,8 +621,8 @@ impl Drop for SocketFd {
 // <https://doc.rust-lang.org/stable/std/primitive.pointer.html#method.len>
 //
 // FIXME: In 1.79 this was stabilized. Bump MSRV fine?
-fn ptr_len(ptr: *mut [u8]) -> usize {
-    unsafe { (*(ptr as *mut [()])).len() }
+pub(crate) fn ptr_len(ptr: *mut [u8]) -> usize {
+    unsafe { (&*(ptr as *mut [()])).len() }
 }
 
 impl Socket {
@@ -0,0 +1,167 @@
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+use alloc::sync::Arc;
+
+use crate::xsk::{
+    BorrowedUmem, Bound, ConfigError, ConfigField, DeviceQueue, FillManager, IfInfo, ReadComplete,
+    ReadRx, RingRx, RingTx, SetupError, SharedTx, Socket, SocketConfig, SocketFd, Umem, UmemConfig,
+    User, WriteFill, WriteTx,
+};
+use crate::Errno;
+
+impl<'area> BorrowedUmem<'area> {
+    /// Create a new Umem ring in a borrowed buffer.
+    ///
+    /// The buffer must be page aligned, such as a `#[repr(align(4096))]` struct, otherwise this
+    /// fails with a [`SetupError::Config`] for the area.
+    ///
+    /// # Safety
+    ///
+    /// The kernel accesses the buffer for as long as the socket of the `Umem`, or any socket, queue
+    /// or ring derived from it, is open. These are closed when the returned value and every
+    /// [`Bound`] derived from it are dropped, which the borrow enforces to happen before the buffer
+    /// is used otherwise. The caller must ensure that they are actually dropped: none of them may
+    /// be leaked, for instance with `core::mem::forget` or in a reference cycle.
+    pub unsafe fn new(config: UmemConfig, area: &'area mut [u8]) -> Result<Self, SetupError> {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+
+        if (area.as_ptr() as usize & (page_size - 1)) != 0 {
+            let err = ConfigError::new(ConfigField::Area, "must be page aligned");
+            return Err(err.into());
+        }
+
+        let area = NonNull::from(area);
+        // Safety: the area is aligned and valid for its length. It stays valid for the lifetime
+        // of the borrow, and the caller ensures that the `Umem` and all objects derived through us
+        // are dropped before it ends.
+        let mut umem = Umem::with_area(config, area, None)?;
+        umem.borrowed = true;
+
+        Ok(BorrowedUmem {
+            umem,
+            area: PhantomData,
+        })
+    }
+
+    /// Create a new socket for a given interface, see [`Socket::new`].
+    pub fn socket(&self, interface: &IfInfo) -> Result<Bound<'area, Socket>, Errno> {
+        let fd = Arc::new(SocketFd::new()?);
+        let socket = Socket::with_xdp_socket(interface, fd, None, true)?;
+        Ok(Bound::new(socket))
+    }
+
+    /// Create a socket using the FD of the `umem`, see [`Socket::with_shared`].
+    pub fn socket_shared(&self, interface: &IfInfo) -> Result<Bound<'area, Socket>, Errno> {
+        let fd = self.umem.fd.clone();
+        let socket = Socket::with_xdp_socket(interface, fd, None, true)?;
+        Ok(Bound::new(socket))
+    }
+
+    /// Configure the fill and completion queue for a interface queue, see [`Umem::fq_cq`].
+    pub fn fq_cq(
+        &self,
+        interface: &Bound<'area, Socket>,
+    ) -> Result<Bound<'area, DeviceQueue>, Errno> {
+        self.umem.device_queue(&interface.inner).map(Bound::new)
+    }
+
+    /// Configure the device address for a socket, see [`Umem::rx_tx`].
+    pub fn rx_tx(
+        &self,
+        interface: &Bound<'area, Socket>,
+        config: &SocketConfig,
+    ) -> Result<Bound<'area, User>, SetupError> {
+        self.umem.user(&interface.inner, config).map(Bound::new)
+    }
+
+    /// Activate a socket with by binding it to a device, see [`Umem::bind`].
+    pub fn bind(&self, interface: &Bound<'area, User>) -> Result<(), Errno> {
+        Umem::bind_at(&interface.inner, &self.umem.fd)
+    }
+}
+
+impl<'area, T> Bound<'area, T> {
+    fn new(inner: T) -> Self {
+        Bound {
+            inner,
+            area: PhantomData,
+        }
+    }
+}
+
+impl<'area> Bound<'area, DeviceQueue> {
+    /// Bind the socket to a device queue, activate rx/tx queues, see [`DeviceQueue::bind`].
+    pub fn bind(&self, interface: &Bound<'area, User>) -> Result<(), Errno> {
+        Umem::bind_at(&interface.inner, &self.inner.socket.fd)
+    }
+
+    /// Prepare some buffers for the fill ring, see [`DeviceQueue::fill`].
+    pub fn fill(&mut self, max: u32) -> WriteFill<'_> {
+        self.inner.fill(max)
+    }
+
+    /// Reap some buffers from the completion ring, see [`DeviceQueue::complete`].
+    pub fn complete(&mut self, n: u32) -> ReadComplete<'_> {
+        self.inner.complete(n)
+    }
+
+    /// Move completed buffers straight back into the fill ring, see [`DeviceQueue::recycle`].
+    pub fn recycle(&mut self, max: u32) -> u32 {
+        self.inner.recycle(max)
+    }
+
+    /// Top up the fill ring if it reached the low watermark, see [`FillManager::refill`].
+    pub fn refill(
+        &mut self,
+        manager: &FillManager,
+        source: impl FnOnce(&mut WriteFill<'_>) -> u32,
+    ) -> u32 {
+        manager.refill(&mut self.inner, source)
+    }
+}
+
+impl<'area> Bound<'area, User> {
+    /// Map the RX ring into memory, returning a handle. See [`User::map_rx`].
+    pub fn map_rx(&self) -> Result<Bound<'area, RingRx>, Errno> {
+        self.inner.rx_ring().map(Bound::new)
+    }
+
+    /// Map the TX ring into memory, returning a handle. See [`User::map_tx`].
+    pub fn map_tx(&self) -> Result<Bound<'area, RingTx>, Errno> {
+        self.inner.tx_ring().map(Bound::new)
+    }
+}
+
+impl Bound<'_, RingRx> {
+    /// Receive some buffers, see [`RingRx::receive`].
+    pub fn receive(&mut self, n: u32) -> ReadRx<'_> {
+        self.inner.receive(n)
+    }
+}
+
+impl Bound<'_, RingTx> {
+    /// Transmit some buffers, see [`RingTx::transmit`].
+    pub fn transmit(&mut self, n: u32) -> WriteTx<'_> {
+        self.inner.transmit(n)
+    }
+
+    /// Share the ring between threads for the duration of the borrow, see [`RingTx::share`].
+    pub fn share(&mut self) -> SharedTx<'_> {
+        self.inner.share()
+    }
+}
+
+impl core::ops::Deref for BorrowedUmem<'_> {
+    type Target = Umem;
+    fn deref(&self) -> &Umem {
+        &self.umem
+    }
+}
+
+impl<T> core::ops::Deref for Bound<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
@@ -0,0 +1,100 @@
+use crate::xdp::XdpStatisticsV2;
+use crate::xsk::{DeviceQueue, FillManager, RingProd, WriteFill};
+use crate::Errno;
+
+impl FillManager {
+    /// Create a manager with a low and a high watermark, in number of fill ring entries.
+    ///
+    /// Fails with `EINVAL` if the high watermark is zero or below the low watermark. A high
+    /// watermark above the size of the fill ring is allowed, the ring is then topped up until it
+    /// is full.
+    pub fn new(low: u32, high: u32) -> Result<Self, Errno> {
+        if high == 0 || low > high {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(FillManager {
+            low,
+            high,
+            empty_descs: None,
+        })
+    }
+
+    /// The level at or below which the fill ring is topped up.
+    pub fn low(&self) -> u32 {
+        self.low
+    }
+
+    /// The level to which the fill ring is topped up.
+    pub fn high(&self) -> u32 {
+        self.high
+    }
+
+    /// Top up the fill ring of a queue, if it reached the low watermark.
+    ///
+    /// The source is called with a writer that has capacity for the frames missing to the high
+    /// watermark, for instance `|writer| pool.fill(writer)` with a [`crate::FramePool`] or
+    /// `|writer| writer.insert(frames.by_ref())` with an iterator of addresses. It may insert
+    /// fewer frames if it runs out, and returns the number of frames it inserted, which is checked
+    /// in debug builds. The written frames are committed afterwards. Returns the number of frames
+    /// that were committed, zero if the ring is above the low watermark and the source was not
+    /// called.
+    pub fn refill(
+        &self,
+        queue: &mut DeviceQueue,
+        source: impl FnOnce(&mut WriteFill<'_>) -> u32,
+    ) -> u32 {
+        self.refill_ring(&mut queue.fcq.prod, source)
+    }
+
+    pub(crate) fn refill_ring(
+        &self,
+        ring: &mut RingProd,
+        source: impl FnOnce(&mut WriteFill<'_>) -> u32,
+    ) -> u32 {
+        let level = ring.count_pending();
+
+        if level > self.low {
+            return 0;
+        }
+
+        // Refresh the cached consumer head if it does not show the space that is missing.
+        let missing = self.high.saturating_sub(level);
+        ring.count_free(missing);
+
+        let mut writer = WriteFill::new(ring, missing);
+        if writer.capacity() == 0 {
+            return 0;
+        }
+
+        let reported = source(&mut writer);
+        let written = writer.capacity() - writer.remaining();
+        debug_assert_eq!(
+            reported, written,
+            "the source of the refill reported a different number of frames than it inserted",
+        );
+        writer.commit();
+        written
+    }
+
+    /// Query the statistics of a queue for fill ring starvation.
+    ///
+    /// Returns the number of descriptors that were dropped because the kernel found the fill
+    /// ring empty, since the previous call. See [`FillManager::observe`].
+    pub fn starvation(&mut self, queue: &DeviceQueue) -> Result<u64, Errno> {
+        let stats = queue.statistics_v2()?;
+        Ok(self.observe(&stats))
+    }
+
+    /// Record the statistics of a socket, returning the increase of `rx_fill_ring_empty_descs`.
+    ///
+    /// The first observation only records the counter and returns zero. Any increase means the
+    /// fill ring ran empty since the previous observation, that is the watermarks are too low,
+    /// or the source of frames does not keep up. Note that the counter is only available on
+    /// Linux 5.9 and later, and always reads zero before.
+    pub fn observe(&mut self, stats: &XdpStatisticsV2) -> u64 {
+        let current = stats.rx_fill_ring_empty_descs;
+        let previous = self.empty_descs.replace(current);
+        previous.map_or(0, |previous| current.wrapping_sub(previous))
+    }
+}
@@ -77,6 +77,63 @@ impl IfInfo {
     pub fn queue_id(&self) -> u32 {
         self.ctx.queue_id
     }
+
+    /// Find the NUMA node of the interface's device.
+    ///
+    /// This reads `/sys/class/net/<ifname>/device/numa_node`. Returns `Ok(None)` if the device is
+    /// not associated with any node, such as virtual devices or systems with a single node. Pin
+    /// the workers of a queue to the CPUs of this node, and allocate the `Umem` on it with
+    /// [`crate::Umem::allocate_near`].
+    pub fn numa_node(&self) -> Result<Option<u32>, Errno> {
+        const PREFIX: &[u8] = b"/sys/class/net/";
+        const SUFFIX: &[u8] = b"/device/numa_node\0";
+
+        let name = self.ifname.iter().position(|&c| c == 0).unwrap_or(0);
+        if name == 0 {
+            return Err(Errno(libc::ENODEV));
+        }
+
+        let mut path = [0u8; PREFIX.len() + libc::IFNAMSIZ + SUFFIX.len()];
+        let name_end = PREFIX.len() + name;
+        path[..PREFIX.len()].copy_from_slice(PREFIX);
+        for (p, &c) in path[PREFIX.len()..name_end].iter_mut().zip(&self.ifname) {
+            *p = c as u8;
+        }
+        path[name_end..][..SUFFIX.len()].copy_from_slice(SUFFIX);
+
+        let fd = unsafe {
+            libc::open(
+                path.as_ptr() as *const libc::c_char,
+                libc::O_RDONLY | libc::O_CLOEXEC,
+            )
+        };
+
+        if fd < 0 {
+            let err = Errno::last_os_error();
+            // Not backed by a physical device.
+            return match err.get_raw() {
+                libc::ENOENT => Ok(None),
+                _ => Err(err),
+            };
+        }
+
+        let mut buf = [0u8; 16];
+        let len = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+        let err = Errno::last_os_error();
+        unsafe { libc::close(fd) };
+
+        if len < 0 {
+            return Err(err);
+        }
+
+        let node = core::str::from_utf8(&buf[..len as usize])
+            .ok()
+            .and_then(|st| st.trim().parse::<i32>().ok())
+            .ok_or(Errno(libc::EINVAL))?;
+
+        // The kernel reports `-1` for devices without affinity.
+        Ok(u32::try_from(node).ok())
+    }
 }
 
 impl SocketMmapOffsets {
@@ -0,0 +1,123 @@
+use core::ops::Range;
+use core::ptr::NonNull;
+
+use alloc::vec::Vec;
+
+use crate::xsk::{ptr_len, BufIdx, Umem, UmemChunk, UmemPartition};
+use crate::Errno;
+
+impl Umem {
+    /// Split all frames into `n` disjoint partitions of consecutive frames.
+    ///
+    /// The frames are distributed as evenly as possible, the first partitions receive one frame
+    /// more than the others if the count is not divisible. If there are fewer frames than
+    /// partitions then some of the partitions are empty.
+    pub fn partition(&self, n: u32) -> Vec<UmemPartition> {
+        if n == 0 {
+            return Vec::new();
+        }
+
+        let total = self.len_frames();
+        let (per, extra) = (total / n, total % n);
+
+        let mut start = 0;
+        (0..n)
+            .map(|idx| {
+                let len = per + u32::from(idx < extra);
+                let range = start..start + len;
+                start = range.end;
+                self.partition_unchecked(range)
+            })
+            .collect()
+    }
+
+    /// Split frames into partitions with the given ranges of frame indices.
+    ///
+    /// Frames not contained in any range are not part of any partition. Fails with `EINVAL` if
+    /// any range is outside the frames of the `Umem`, or if two ranges overlap.
+    pub fn partition_ranges(&self, ranges: &[Range<u32>]) -> Result<Vec<UmemPartition>, Errno> {
+        let total = self.len_frames();
+
+        let mut sorted: Vec<_> = ranges.iter().filter(|range| !range.is_empty()).collect();
+        sorted.sort_by_key(|range| range.start);
+
+        let in_bounds = sorted.iter().all(|range| range.end <= total);
+        let disjoint = sorted.windows(2).all(|w| w[0].end <= w[1].start);
+
+        if !in_bounds || !disjoint {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(ranges
+            .iter()
+            .map(|range| self.partition_unchecked(range.start..range.end.max(range.start)))
+            .collect())
+    }
+
+    fn partition_unchecked(&self, range: Range<u32>) -> UmemPartition {
+        UmemPartition {
+            umem_area: self.umem_area,
+            config: self.config.clone(),
+            start: range.start,
+            end: range.end,
+            area: self.area.clone(),
+        }
+    }
+}
+
+impl UmemPartition {
+    /// The range of frame indices in this partition.
+    pub fn range(&self) -> Range<u32> {
+        self.start..self.end
+    }
+
+    /// The number of frames in this partition.
+    pub fn len_frames(&self) -> u32 {
+        self.end - self.start
+    }
+
+    /// Check if a frame is part of this partition.
+    pub fn contains(&self, idx: BufIdx) -> bool {
+        (self.start..self.end).contains(&idx.0)
+    }
+
+    /// Iterate over all frames of this partition.
+    pub fn frames(&self) -> impl Iterator<Item = BufIdx> {
+        (self.start..self.end).map(BufIdx)
+    }
+
+    /// Get the address associated with a frame of this partition, see [`Umem::frame`].
+    ///
+    /// Returns `None` if the frame is not part of this partition.
+    pub fn frame(&self, idx: BufIdx) -> Option<UmemChunk> {
+        if !self.contains(idx) {
+            return None;
+        }
+
+        let pitch = self.config.frame_size;
+        let offset = u64::from(pitch) * u64::from(idx.0);
+        debug_assert!(offset + u64::from(pitch) <= ptr_len(self.umem_area.as_ptr()) as u64);
+
+        // Safety: the range was checked to be within the frames of the `Umem` on construction.
+        let base = unsafe { self.umem_area.cast::<u8>().as_ptr().offset(offset as isize) };
+        let slice = core::ptr::slice_from_raw_parts_mut(base, pitch as usize);
+        let addr = unsafe { NonNull::new_unchecked(slice) };
+        Some(UmemChunk { addr, offset })
+    }
+
+    /// Find the frame of this partition containing an address reported by the kernel.
+    ///
+    /// See [`Umem::frame_index`]. Returns `None` if the frame is not part of this partition, which
+    /// indicates that the address was handed to the kernel through another partition.
+    pub fn frame_index(&self, addr: u64) -> Option<BufIdx> {
+        let idx = u32::try_from(self.config.frame_index(addr)).ok()?;
+        Some(BufIdx(idx)).filter(|&idx| self.contains(idx))
+    }
+}
+
+// Safety: a partition is immutable after its construction. Its range of frames is disjoint from
+// the ranges of the other partitions of the same call, so no two threads obtain chunks of the same
+// frame through them. The area pointer is only offset and never dereferenced, and the shared
+// `Arc<Mmap>` that keeps an owned area alive is only read.
+unsafe impl Send for UmemPartition {}
+unsafe impl Sync for UmemPartition {}
@@ -0,0 +1,437 @@
+use core::ops::Range;
+use core::ptr::NonNull;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::mmap::Mmap;
+use crate::xdp::XdpDesc;
+use crate::xsk::{
+    ptr_len, BufIdx, FramePool, FrameState, FrameStateError, ReadComplete, ReadRx, Umem, UmemChunk,
+    UmemConfig, UmemPartition, WriteFill, WriteTx,
+};
+
+impl FramePool {
+    /// Create a pool managing all frames of a `Umem`, all of them initially free.
+    ///
+    /// The pool does not borrow the `Umem`. As with [`Umem::frame`], the chunks handed out are
+    /// only valid to access while the `Umem` area is alive.
+    pub fn new(umem: &Umem) -> Self {
+        let count = umem.len_frames();
+        Self::with_frames(umem.umem_area, &umem.config, 0..count, &umem.area)
+    }
+
+    /// Create a pool managing the frames of a partition, all of them initially free.
+    ///
+    /// Frames outside the partition are not part of the pool. In particular, descriptors and
+    /// completions of such frames are reported as an error, as by [`FramePool::frame_of`].
+    pub fn with_partition(partition: &UmemPartition) -> Self {
+        Self::with_frames(
+            partition.umem_area,
+            &partition.config,
+            partition.range(),
+            &partition.area,
+        )
+    }
+
+    fn with_frames(
+        umem_area: NonNull<[u8]>,
+        config: &UmemConfig,
+        frames: Range<u32>,
+        area: &Option<Arc<Mmap>>,
+    ) -> Self {
+        let states = frames.clone().map(|_| FrameState::Free).collect();
+        // Reversed, such that allocation starts with the lowest index.
+        let free = frames.clone().rev().map(BufIdx).collect();
+
+        FramePool {
+            umem_area,
+            config: config.clone(),
+            first: frames.start,
+            states,
+            free,
+            queued: Vec::new(),
+            area: area.clone(),
+        }
+    }
+
+    /// The number of frames managed by this pool.
+    pub fn len_frames(&self) -> u32 {
+        self.states.len() as u32
+    }
+
+    /// The number of frames that are currently free.
+    pub fn len_free(&self) -> u32 {
+        self.free.len() as u32
+    }
+
+    /// Query the state of a frame.
+    ///
+    /// Returns `None` if the index is not a frame of this pool.
+    pub fn state(&self, idx: BufIdx) -> Option<FrameState> {
+        let slot = idx.0.checked_sub(self.first)?;
+        self.states.get(slot as usize).copied()
+    }
+
+    /// Get the address associated with a frame, see [`Umem::frame`].
+    pub fn frame(&self, idx: BufIdx) -> Option<UmemChunk> {
+        self.state(idx)?;
+
+        let offset = u64::from(self.config.frame_size) * u64::from(idx.0);
+        debug_assert!(
+            offset + u64::from(self.config.frame_size) <= ptr_len(self.umem_area.as_ptr()) as u64
+        );
+
+        // Safety: all frames of the pool are within the area, checked by `Umem::len_frames`.
+        let base = unsafe { self.umem_area.cast::<u8>().as_ptr().offset(offset as isize) };
+        let slice = core::ptr::slice_from_raw_parts_mut(base, self.config.frame_size as usize);
+        let addr = unsafe { NonNull::new_unchecked(slice) };
+        Some(UmemChunk { addr, offset })
+    }
+
+    /// Find the frame containing the address of a descriptor or completion.
+    ///
+    /// This resolves the in-chunk offset of addresses in unaligned chunk mode.
+    pub fn frame_of(&self, addr: u64) -> Option<BufIdx> {
+        let idx = self.index_of(addr);
+        self.state(idx)?;
+        Some(idx)
+    }
+
+    /// Take a free frame from the pool.
+    ///
+    /// The frame is then [`FrameState::Allocated`], until it is released, filled or transmitted.
+    pub fn alloc(&mut self) -> Option<BufIdx> {
+        let idx = self.free.pop()?;
+        debug_assert_eq!(self.state(idx), Some(FrameState::Free));
+        *self.state_mut(idx) = FrameState::Allocated;
+        Some(idx)
+    }
+
+    /// Return a frame owned by the user to the pool.
+    ///
+    /// The frame must be [`FrameState::Allocated`] or [`FrameState::Received`].
+    pub fn release(&mut self, idx: BufIdx) -> Result<(), FrameStateError> {
+        self.transition(idx, Self::is_owned, FrameState::Free)?;
+        self.free.push(idx);
+        Ok(())
+    }
+
+    /// Put free frames into the fill ring, as many as fit.
+    ///
+    /// Returns the number of frames inserted into the writer. Commit the writer afterwards, as
+    /// usual. Note that dropping the writer without a commit leaves these frames marked as
+    /// [`FrameState::Fill`] while the kernel never received them.
+    pub fn fill(&mut self, writer: &mut WriteFill<'_>) -> u32 {
+        let count = (writer.remaining() as usize).min(self.free.len());
+        let start = self.free.len() - count;
+
+        for idx in &self.free[start..] {
+            let slot = (idx.0 - self.first) as usize;
+            debug_assert_eq!(self.states[slot], FrameState::Free);
+            self.states[slot] = FrameState::Fill;
+        }
+
+        let frame_size = u64::from(self.config.frame_size);
+        let addrs = self.free[start..]
+            .iter()
+            .rev()
+            .map(|idx| u64::from(idx.0) * frame_size);
+        let n = writer.insert(addrs);
+        debug_assert_eq!(n as usize, count);
+
+        self.free.truncate(start);
+        n
+    }
+
+    /// Put a specific frame owned by the user into the fill ring.
+    ///
+    /// The frame must be [`FrameState::Allocated`] or [`FrameState::Received`]. This recycles a
+    /// received frame without returning it to the pool first. Returns `Ok(false)` if the writer is
+    /// full, in which case the frame's state is not changed.
+    pub fn fill_frame(
+        &mut self,
+        writer: &mut WriteFill<'_>,
+        idx: BufIdx,
+    ) -> Result<bool, FrameStateError> {
+        self.check(idx, Self::is_owned)?;
+
+        if !writer.insert_once(u64::from(idx.0) * u64::from(self.config.frame_size)) {
+            return Ok(false);
+        }
+
+        *self.state_mut(idx) = FrameState::Fill;
+        Ok(true)
+    }
+
+    /// Read the next descriptor from the receive ring, marking its frame as received.
+    ///
+    /// The frame must have been in the fill ring. Returns `Ok(None)` if there are no more
+    /// descriptors available in the reader. On error, the descriptor is not read and remains the
+    /// next one in the reader.
+    pub fn receive(
+        &mut self,
+        reader: &mut ReadRx<'_>,
+    ) -> Result<Option<(BufIdx, XdpDesc)>, FrameStateError> {
+        let desc = match reader.as_slices().0.first() {
+            Some(&desc) => desc,
+            None => return Ok(None),
+        };
+
+        let idx = self.frame_of_checked(desc.addr)?;
+        self.transition(idx, |st| st == FrameState::Fill, FrameState::Received)?;
+        reader.mark_read(1);
+        Ok(Some((idx, desc)))
+    }
+
+    /// Insert a descriptor into the transmit ring, marking its frame as queued.
+    ///
+    /// The frame containing the descriptor must be [`FrameState::Allocated`] or
+    /// [`FrameState::Received`]. Returns `Ok(false)` if the writer is full, in which case the
+    /// frame's state is not changed. The frame is [`FrameState::Transmit`] until the writer is
+    /// committed with [`FramePool::commit_transmit`].
+    pub fn transmit(
+        &mut self,
+        writer: &mut WriteTx<'_>,
+        desc: XdpDesc,
+    ) -> Result<bool, FrameStateError> {
+        let idx = self.frame_of_checked(desc.addr)?;
+        self.check(idx, Self::is_owned)?;
+
+        if !writer.insert_once(desc) {
+            return Ok(false);
+        }
+
+        *self.state_mut(idx) = FrameState::Transmit;
+        self.queued.push(idx);
+        Ok(true)
+    }
+
+    /// Commit a transmit writer, marking all queued frames as sent.
+    ///
+    /// All frames queued with [`FramePool::transmit`] since the last commit or cancel are then
+    /// [`FrameState::Sent`], awaiting their completion. Use one writer at a time with the pool,
+    /// the frames are not associated with a particular writer.
+    pub fn commit_transmit(&mut self, writer: &mut WriteTx<'_>) {
+        writer.commit();
+
+        for idx in self.queued.drain(..) {
+            let slot = (idx.0 - self.first) as usize;
+            debug_assert_eq!(self.states[slot], FrameState::Transmit);
+            self.states[slot] = FrameState::Sent;
+        }
+    }
+
+    /// Return all queued frames to the user, after their writer was dropped without a commit.
+    ///
+    /// All frames queued with [`FramePool::transmit`] since the last commit or cancel are then
+    /// [`FrameState::Allocated`] again.
+    pub fn cancel_transmit(&mut self) {
+        for idx in self.queued.drain(..) {
+            let slot = (idx.0 - self.first) as usize;
+            debug_assert_eq!(self.states[slot], FrameState::Transmit);
+            self.states[slot] = FrameState::Allocated;
+        }
+    }
+
+    /// Read all addresses from the completion ring, returning their frames to the pool.
+    ///
+    /// Each frame must have been [`FrameState::Sent`]. Returns the number of frames that were
+    /// freed. On error, the offending address has been read while previous ones are freed.
+    pub fn complete(&mut self, reader: &mut ReadComplete<'_>) -> Result<u32, FrameStateError> {
+        let mut n = 0;
+
+        while let Some(addr) = reader.read() {
+            let idx = self.frame_of_checked(addr)?;
+            self.transition(idx, |st| st == FrameState::Sent, FrameState::Free)?;
+            self.free.push(idx);
+            n += 1;
+        }
+
+        Ok(n)
+    }
+
+    /// The state of a frame that is part of the pool.
+    fn state_mut(&mut self, idx: BufIdx) -> &mut FrameState {
+        &mut self.states[(idx.0 - self.first) as usize]
+    }
+
+    fn is_owned(state: FrameState) -> bool {
+        matches!(state, FrameState::Allocated | FrameState::Received)
+    }
+
+    fn index_of(&self, addr: u64) -> BufIdx {
+        let idx = self.config.frame_index(addr);
+        BufIdx(u32::try_from(idx).unwrap_or(u32::MAX))
+    }
+
+    fn frame_of_checked(&self, addr: u64) -> Result<BufIdx, FrameStateError> {
+        let idx = self.index_of(addr);
+        self.check(idx, |_| true)?;
+        Ok(idx)
+    }
+
+    fn check(
+        &self,
+        idx: BufIdx,
+        valid: impl FnOnce(FrameState) -> bool,
+    ) -> Result<(), FrameStateError> {
+        match self.state(idx) {
+            Some(state) if valid(state) => Ok(()),
+            state => Err(FrameStateError { frame: idx, state }),
+        }
+    }
+
+    fn transition(
+        &mut self,
+        idx: BufIdx,
+        valid: impl FnOnce(FrameState) -> bool,
+        next: FrameState,
+    ) -> Result<(), FrameStateError> {
+        self.check(idx, valid)?;
+        *self.state_mut(idx) = next;
+        Ok(())
+    }
+}
+
+impl FrameStateError {
+    /// The frame on which the operation was attempted.
+    ///
+    /// For addresses outside of the pool this is the index the address would have, saturated to
+    /// `u32::MAX`.
+    pub fn frame(&self) -> BufIdx {
+        self.frame
+    }
+
+    /// The state the frame was in, or `None` if the frame is not part of the pool.
+    pub fn state(&self) -> Option<FrameState> {
+        self.state
+    }
+}
+
+impl core::fmt::Display for FrameStateError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.state {
+            Some(state) => write!(
+                f,
+                "frame {} is in state {:?}, invalid for this operation",
+                self.frame.0, state
+            ),
+            None => write!(f, "frame {} is not part of the pool", self.frame.0),
+        }
+    }
+}
+
+// Safety: the frame states, the free list and the transmit queue are vectors owned by the pool and
+// only modified through `&mut self`, a shared pool reads them at most. The area pointer is not
+// dereferenced, only offset to form the chunks of `FramePool::frame`, and the area outlives the
+// pool through `area` or the contract of `Umem::new`. It is thus not tied to the creating thread.
+unsafe impl Send for FramePool {}
+unsafe impl Sync for FramePool {}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use alloc::vec;
+    use core::num::NonZeroU32;
+    use core::ptr::NonNull;
+
+    use crate::xdp::XdpDesc;
+    use crate::xsk::{FramePool, FrameState, Simulator, SocketConfig, UmemConfig};
+
+    const FRAME: u64 = 4096;
+
+    fn setup(area: &mut [u8]) -> (FramePool, Simulator) {
+        let config = UmemConfig::default();
+        let pool = FramePool::with_frames(NonNull::from(area), &config, 0..4, &None);
+
+        let socket = SocketConfig {
+            rx_size: NonZeroU32::new(4),
+            tx_size: NonZeroU32::new(4),
+            bind_flags: 0,
+        };
+
+        (pool, Simulator::new(&config, &socket).unwrap())
+    }
+
+    #[test]
+    fn transmit_awaits_commit() {
+        let mut area = vec![0; 4 * FRAME as usize];
+        let (mut pool, mut sim) = setup(&mut area);
+
+        let idx = pool.alloc().unwrap();
+        let desc = pool.frame(idx).unwrap().as_xdp_with_len(60);
+
+        let mut writer = sim.transmit(1);
+        assert!(pool.transmit(&mut writer, desc).unwrap());
+        assert_eq!(pool.state(idx), Some(FrameState::Transmit));
+        pool.commit_transmit(&mut writer);
+        drop(writer);
+        assert_eq!(pool.state(idx), Some(FrameState::Sent));
+
+        assert_eq!(sim.take_tx().map(|desc| desc.addr), Some(desc.addr));
+        assert!(sim.push_completion(desc.addr));
+        let mut reader = sim.complete(1);
+        assert_eq!(pool.complete(&mut reader).unwrap(), 1);
+        reader.release();
+        assert_eq!(pool.state(idx), Some(FrameState::Free));
+    }
+
+    #[test]
+    fn transmit_cancelled() {
+        let mut area = vec![0; 4 * FRAME as usize];
+        let (mut pool, mut sim) = setup(&mut area);
+
+        let idx = pool.alloc().unwrap();
+        let desc = pool.frame(idx).unwrap().as_xdp_with_len(60);
+
+        let mut writer = sim.transmit(1);
+        assert!(pool.transmit(&mut writer, desc).unwrap());
+        drop(writer);
+        pool.cancel_transmit();
+
+        assert_eq!(pool.state(idx), Some(FrameState::Allocated));
+        assert!(sim.take_tx().is_none());
+    }
+
+    #[test]
+    fn completion_before_commit() {
+        let mut area = vec![0; 4 * FRAME as usize];
+        let (mut pool, mut sim) = setup(&mut area);
+
+        let idx = pool.alloc().unwrap();
+        let desc = pool.frame(idx).unwrap().as_xdp_with_len(60);
+
+        let mut writer = sim.transmit(1);
+        assert!(pool.transmit(&mut writer, desc).unwrap());
+        drop(writer);
+
+        assert!(sim.push_completion(desc.addr));
+        let err = pool.complete(&mut sim.complete(1)).unwrap_err();
+        assert_eq!(err.frame().0, idx.0);
+        assert_eq!(err.state(), Some(FrameState::Transmit));
+    }
+
+    #[test]
+    fn receive_error_leaves_descriptor() {
+        let mut area = vec![0; 4 * FRAME as usize];
+        let (mut pool, mut sim) = setup(&mut area);
+
+        // Frame 1 was never given to the kernel, frame 0 was.
+        let mut writer = sim.fill(1);
+        assert_eq!(pool.fill(&mut writer), 1);
+        writer.commit();
+        drop(writer);
+
+        let bogus = XdpDesc {
+            addr: FRAME,
+            len: 60,
+            options: 0,
+        };
+
+        assert!(sim.push_rx(bogus));
+        let mut reader = sim.receive(1);
+        let err = pool.receive(&mut reader).unwrap_err();
+        assert_eq!(err.frame().0, 1);
+        assert_eq!(reader.read().map(|desc| desc.addr), Some(bogus.addr));
+    }
+}
@@ -2,7 +2,9 @@ use core::sync::atomic::Ordering;
 use core::{ops::RangeInclusive, ptr::NonNull};
 
 use crate::xdp::{XdpDesc, XdpRingOffsets};
-use crate::xsk::{BufIdx, RingCons, RingProd, SocketFd, SocketMmapOffsets, XskRing};
+use crate::xsk::{
+    BufIdx, RingCons, RingProd, RingState, RingTx, SocketFd, SocketMmapOffsets, XskRing,
+};
 use crate::{Errno, LastErrno};
 
 impl XskRing {
@@ -42,9 +44,147 @@ impl XskRing {
             flags,
             cached_producer: producer.load(Ordering::Relaxed),
             cached_consumer: consumer.load(Ordering::Relaxed),
+            #[cfg(feature = "ring-invariants")]
+            outstanding: 0,
         }
     }
 
+    /// Record entries that were reserved or peeked.
+    #[cfg(feature = "ring-invariants")]
+    fn acquire(&mut self, nb: u32) {
+        self.outstanding += nb;
+        assert!(
+            self.outstanding <= self.size,
+            "Ring invariant violated: {} entries outstanding in a ring of {}",
+            self.outstanding,
+            self.size,
+        );
+    }
+
+    /// Record entries that were submitted, released, or cancelled.
+    #[cfg(feature = "ring-invariants")]
+    fn retire(&mut self, nb: u32, op: &str, acquired: &str) {
+        assert!(
+            nb <= self.outstanding,
+            "Ring invariant violated: {op} {nb} entries but only {} were {acquired}",
+            self.outstanding,
+        );
+        self.outstanding -= nb;
+    }
+
+    /// Check the heads of the ring, after loading one of them from the kernel.
+    #[cfg(feature = "ring-invariants")]
+    pub(super) fn check_heads(&self, producer: u32, consumer: u32) {
+        let pending = producer.wrapping_sub(consumer);
+        assert!(
+            pending <= self.size,
+            "Ring invariant violated: producer {producer} is {pending} entries ahead of consumer \
+             {consumer} in a ring of {}",
+            self.size,
+        );
+    }
+
+    #[cfg(not(feature = "ring-invariants"))]
+    #[inline(always)]
+    fn acquire(&mut self, _: u32) {}
+
+    #[cfg(not(feature = "ring-invariants"))]
+    #[inline(always)]
+    fn retire(&mut self, _: u32, _: &str, _: &str) {}
+
+    #[cfg(not(feature = "ring-invariants"))]
+    #[inline(always)]
+    pub(super) fn check_heads(&self, _: u32, _: u32) {}
+
+    /// Get the entries `idx..idx+count` of the ring, as up to two contiguous slices.
+    ///
+    /// The second slice is empty unless the range wraps around the end of the ring.
+    ///
+    /// # Safety
+    ///
+    /// The type `T` must be the type of entries of this ring, and `count` must not exceed its
+    /// size.
+    pub(super) unsafe fn slots<T>(&self, idx: BufIdx, count: u32) -> [NonNull<[T]>; 2] {
+        debug_assert!(count <= self.size);
+        let start = idx.0 & self.mask;
+        let head = count.min(self.size - start);
+
+        let base = self.ring.cast::<T>().as_ptr();
+        let first = core::ptr::slice_from_raw_parts_mut(base.add(start as usize), head as usize);
+        let second = core::ptr::slice_from_raw_parts_mut(base, (count - head) as usize);
+
+        // Safety: both are derived from the non-null base of the ring.
+        [
+            NonNull::new_unchecked(first),
+            NonNull::new_unchecked(second),
+        ]
+    }
+
+    fn state(&self, user_produces: bool) -> RingState {
+        RingState {
+            producer: self.producer.load(Ordering::Relaxed),
+            consumer: self.consumer.load(Ordering::Relaxed),
+            cached_producer: self.cached_producer,
+            cached_consumer: self.cached_consumer,
+            size: self.size,
+            mask: self.mask,
+            flags: unsafe { *self.flags.as_ptr() },
+            user_produces,
+        }
+    }
+
+    /// Construct a ring in an anonymous mapping, not shared with the kernel.
+    ///
+    /// The layout mimics the kernel's, with the producer, consumer and flags on separate cache
+    /// lines in front of the entries. Returns a second view of the same ring, for the side that
+    /// stands in for the kernel. It must not outlive the mapping.
+    #[cfg(feature = "sim")]
+    fn anonymous(count: u32, sz: u64) -> Result<(Self, Self, NonNull<[u8]>), Errno> {
+        const OFFSETS: XdpRingOffsets = XdpRingOffsets {
+            producer: 0,
+            consumer: 64,
+            flags: 128,
+            desc: 192,
+        };
+
+        if !count.is_power_of_two() {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        let len = (OFFSETS.desc + u64::from(count) * sz) as usize;
+
+        let mmap = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+
+        if mmap == libc::MAP_FAILED {
+            return Err(LastErrno)?;
+        }
+
+        assert!(!mmap.is_null());
+        // Safety: as by MMap this pointer is valid.
+        let mmap_addr = core::ptr::slice_from_raw_parts_mut(mmap as *mut u8, len);
+        let mmap_addr = unsafe { NonNull::new_unchecked(mmap_addr) };
+        let nn = mmap_addr.cast();
+
+        // Safety: the mapping is valid for the offsets and zeroed, i.e. an empty ring.
+        let (user, kernel) = unsafe {
+            (
+                XskRing::new(nn, &OFFSETS, count),
+                XskRing::new(nn, &OFFSETS, count),
+            )
+        };
+
+        Ok((user, kernel, mmap_addr))
+    }
+
     unsafe fn map(
         fd: &SocketFd,
         off: &XdpRingOffsets,
@@ -80,6 +220,16 @@ impl XskRing {
 }
 
 impl RingProd {
+    /// Create a ring in memory only, for a simulation of the kernel side.
+    ///
+    /// Returns the ring along with a view for the consumer side. See [`XskRing::anonymous`].
+    #[cfg(feature = "sim")]
+    pub(super) fn anonymous<T>(count: u32) -> Result<(Self, XskRing), Errno> {
+        let (inner, kernel, mmap_addr) =
+            XskRing::anonymous(count, core::mem::size_of::<T>() as u64)?;
+        Ok((RingProd { inner, mmap_addr }, kernel))
+    }
+
     /// # Safety
     ///
     /// The caller must only pass `fd` and `off` if they correspond as they were returned by the
@@ -144,6 +294,21 @@ impl RingProd {
         unsafe { NonNull::new_unchecked(base.offset(offset)) }
     }
 
+    /// Take a snapshot of the state of the ring, see [`RingState`].
+    pub fn state(&self) -> RingState {
+        self.inner.state(true)
+    }
+
+    /// Return the addresses of `count` entries starting at `idx`, as up to two slices.
+    ///
+    /// # Safety
+    ///
+    /// The type `T` must be `u64` for a fill ring and `XdpDesc` for a transmit ring. Further, the
+    /// caller guarantees that the entries are valid for the ring, i.e. have been reserved.
+    pub(crate) unsafe fn slots<T>(&self, idx: BufIdx, count: u32) -> [NonNull<[T]>; 2] {
+        unsafe { self.inner.slots(idx, count) }
+    }
+
     /// Query for up to `nb` free entries.
     ///
     /// Serves small requests based on cached state about the kernel's consumer head. Large
@@ -159,6 +324,10 @@ impl RingProd {
         }
 
         self.inner.cached_consumer = self.inner.consumer.load(Ordering::Acquire);
+        self.inner.check_heads(
+            self.inner.producer.load(Ordering::Relaxed),
+            self.inner.cached_consumer,
+        );
         // No-op module the size, but ensures our view of the consumer is always ahead of the
         // producer, no matter buffer counts and mask.
         // TODO: actually, I don't _quite_ understand. This algorithm is copied from libxdp.
@@ -182,6 +351,7 @@ impl RingProd {
         let free = free.min(end);
         *idx = BufIdx(self.inner.cached_producer);
         self.inner.cached_producer += free;
+        self.inner.acquire(free);
 
         free
     }
@@ -190,6 +360,7 @@ impl RingProd {
     ///
     /// If passed a smaller number, the remaining reservation stays active.
     pub fn cancel(&mut self, nb: u32) {
+        self.inner.retire(nb, "cancelled", "reserved");
         self.inner.cached_producer -= nb;
     }
 
@@ -201,6 +372,7 @@ impl RingProd {
     /// TODO: interestingly this could be implemented on a shared reference. But is doing so
     /// useful? There's no affirmation that the _intended_ buffers are submitted.
     pub fn submit(&mut self, nb: u32) {
+        self.inner.retire(nb, "submitted", "reserved");
         // We are the only writer, all other writes are ordered before.
         let cur = self.inner.producer.load(Ordering::Relaxed);
         // When the kernel reads it, all writes to buffers must be ordered before this write to the
@@ -230,6 +402,16 @@ impl RingProd {
 }
 
 impl RingCons {
+    /// Create a ring in memory only, for a simulation of the kernel side.
+    ///
+    /// Returns the ring along with a view for the producer side. See [`XskRing::anonymous`].
+    #[cfg(feature = "sim")]
+    pub(super) fn anonymous<T>(count: u32) -> Result<(Self, XskRing), Errno> {
+        let (inner, kernel, mmap_addr) =
+            XskRing::anonymous(count, core::mem::size_of::<T>() as u64)?;
+        Ok((RingCons { inner, mmap_addr }, kernel))
+    }
+
     /// Create a completion ring.
     /// # Safety
     ///
@@ -296,6 +478,21 @@ impl RingCons {
         unsafe { NonNull::new_unchecked(base.offset(offset)) }
     }
 
+    /// Return the addresses of `count` entries starting at `idx`, as up to two slices.
+    ///
+    /// # Safety
+    ///
+    /// The type `T` must be `u64` for a completion ring and `XdpDesc` for a receive ring. Further,
+    /// the caller guarantees that the entries are valid for the ring, i.e. have been peeked.
+    pub(crate) unsafe fn slots<T>(&self, idx: BufIdx, count: u32) -> [NonNull<[T]>; 2] {
+        unsafe { self.inner.slots(idx, count) }
+    }
+
+    /// Take a snapshot of the state of the ring, see [`RingState`].
+    pub fn state(&self) -> RingState {
+        self.inner.state(false)
+    }
+
     /// Find the number of available entries.
     ///
     /// Any count lower than `expected` will try to refresh the consumer.
@@ -309,6 +506,10 @@ impl RingCons {
             let new_val = self.inner.producer.load(Ordering::Relaxed);
             available = new_val.wrapping_sub(self.inner.cached_consumer);
             self.inner.cached_producer = self.inner.producer.load(Ordering::Acquire);
+            self.inner.check_heads(
+                self.inner.cached_producer,
+                self.inner.consumer.load(Ordering::Relaxed),
+            );
         }
 
         available
@@ -338,6 +539,7 @@ impl RingCons {
         let count = count.min(end);
         *idx = BufIdx(self.inner.cached_consumer);
         self.inner.cached_consumer += count;
+        self.inner.acquire(count);
 
         count
     }
@@ -346,6 +548,7 @@ impl RingCons {
     ///
     /// If passed a smaller number, the remaining reservation stays active.
     pub fn cancel(&mut self, nb: u32) {
+        self.inner.retire(nb, "cancelled", "peeked");
         self.inner.cached_consumer -= nb;
     }
 
@@ -354,6 +557,7 @@ impl RingCons {
     /// TODO: interestingly this could be implemented on a shared reference. But is doing so
     /// useful? There's no affirmation that the _intended_ buffers are submitted.
     pub fn release(&mut self, nb: u32) {
+        self.inner.retire(nb, "released", "peeked");
         // We are the only writer, all other writes are ordered before.
         let cur = self.inner.consumer.load(Ordering::Relaxed);
         // All our reads from buffers must be ordered before this write to the head, this
@@ -389,3 +593,109 @@ impl Drop for RingCons {
 unsafe impl Send for XskRing {}
 unsafe impl Send for RingProd {}
 unsafe impl Send for RingCons {}
+
+impl RingState {
+    /// The number of entries published by the producer, not yet released by the consumer.
+    pub fn pending(&self) -> u32 {
+        self.producer.wrapping_sub(self.consumer)
+    }
+
+    /// The number of entries we reserved or peeked, but did not submit or release yet.
+    pub fn outstanding(&self) -> u32 {
+        if self.user_produces {
+            self.cached_producer.wrapping_sub(self.producer)
+        } else {
+            self.cached_consumer.wrapping_sub(self.consumer)
+        }
+    }
+
+    /// If the kernel asks to be woken up to make progress on the ring.
+    pub fn needs_wakeup(&self) -> bool {
+        self.flags & RingTx::XDP_RING_NEED_WAKEUP != 0
+    }
+}
+
+impl core::fmt::Debug for RingState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (side, outstanding) = if self.user_produces {
+            (
+                "user space produces, the kernel consumes",
+                "reserved, not yet submitted",
+            )
+        } else {
+            (
+                "the kernel produces, user space consumes",
+                "peeked, not yet released",
+            )
+        };
+
+        f.debug_struct("RingState")
+            .field("side", &format_args!("{side}"))
+            .field("producer", &self.producer)
+            .field("consumer", &self.consumer)
+            .field("cached_producer", &self.cached_producer)
+            .field("cached_consumer", &self.cached_consumer)
+            .field("size", &self.size)
+            .field("mask", &format_args!("{:#x}", self.mask))
+            .field(
+                "flags",
+                &format_args!("{:#x} (need wakeup: {})", self.flags, self.needs_wakeup()),
+            )
+            .field(
+                "pending",
+                &format_args!(
+                    "{} of {} entries published, not yet consumed",
+                    self.pending(),
+                    self.size
+                ),
+            )
+            .field(
+                "outstanding",
+                &format_args!("{} entries {outstanding}", self.outstanding()),
+            )
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "ring-invariants", feature = "sim"))]
+mod tests {
+    use core::sync::atomic::Ordering;
+
+    use crate::xsk::{BufIdx, RingCons, RingProd};
+
+    #[test]
+    #[should_panic = "Ring invariant violated: submitted 2 entries but only 1 were reserved"]
+    fn submit_more_than_reserved() {
+        let (mut ring, _kernel) = RingProd::anonymous::<u64>(8).unwrap();
+        let mut idx = BufIdx(0);
+        assert_eq!(ring.reserve(1..=1, &mut idx), 1);
+        ring.submit(2);
+    }
+
+    #[test]
+    #[should_panic = "Ring invariant violated: cancelled 3 entries but only 2 were reserved"]
+    fn cancel_more_than_reserved() {
+        let (mut ring, _kernel) = RingProd::anonymous::<u64>(8).unwrap();
+        let mut idx = BufIdx(0);
+        assert_eq!(ring.reserve(2..=2, &mut idx), 2);
+        ring.cancel(3);
+    }
+
+    #[test]
+    #[should_panic = "Ring invariant violated: released 2 entries but only 1 were peeked"]
+    fn release_more_than_peeked() {
+        let (mut ring, kernel) = RingCons::anonymous::<u64>(8).unwrap();
+        // As the kernel, publish two entries of which we only peek one.
+        kernel.producer.store(2, Ordering::Release);
+        let mut idx = BufIdx(0);
+        assert_eq!(ring.peek(1..=1, &mut idx), 1);
+        ring.release(2);
+    }
+
+    #[test]
+    #[should_panic = "Ring invariant violated: cancelled 1 entries but only 0 were peeked"]
+    fn cancel_more_than_peeked() {
+        let (mut ring, _kernel) = RingCons::anonymous::<u64>(8).unwrap();
+        ring.cancel(1);
+    }
+}
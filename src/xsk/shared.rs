@@ -0,0 +1,123 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::xdp::XdpDesc;
+use crate::xsk::user::{copy_to_ring, wake_send};
+use crate::xsk::{BufIdx, RingProd, SharedTx, SocketFd, WakeOutcome};
+use crate::Errno;
+
+impl<'ring> SharedTx<'ring> {
+    pub(crate) fn new(ring: &'ring mut RingProd, fd: Option<&'ring SocketFd>) -> Self {
+        // Any reservation has been cancelled or submitted when its writer was dropped.
+        let claimed = ring.inner.producer.load(Ordering::Relaxed);
+        debug_assert_eq!(claimed, ring.inner.cached_producer);
+
+        SharedTx {
+            ring,
+            claimed: AtomicU32::new(claimed),
+            fd,
+        }
+    }
+}
+
+impl SharedTx<'_> {
+    /// Insert a prefix of the descriptors, and publish them to the kernel.
+    ///
+    /// Returns the number of descriptors that were inserted, which is less than the length of the
+    /// slice if the ring is full. The descriptors of one call are contiguous in the ring but may be
+    /// interleaved with the descriptors of other calls, so each call should contain complete
+    /// packets.
+    ///
+    /// If another thread claimed slots before us but has not yet published them, this spins until
+    /// it does.
+    pub fn insert_slice(&self, descs: &[XdpDesc]) -> u32 {
+        let inner = &self.ring.inner;
+        let want = u32::try_from(descs.len()).unwrap_or(u32::MAX);
+
+        let mut start = self.claimed.load(Ordering::Acquire);
+        let count = loop {
+            // Acquire, the kernel must be done reading the slots that we overwrite.
+            let consumer = inner.consumer.load(Ordering::Acquire);
+            inner.check_heads(start, consumer);
+            let free = inner.size.saturating_sub(start.wrapping_sub(consumer));
+            let count = want.min(free);
+
+            if count == 0 {
+                return 0;
+            }
+
+            match self.claimed.compare_exchange_weak(
+                start,
+                start.wrapping_add(count),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break count,
+                Err(current) => start = current,
+            }
+        };
+
+        // Safety: the slots were claimed by us alone, and are not read by the kernel until we
+        // publish them below.
+        unsafe {
+            let slots = self.ring.slots(BufIdx(start), count);
+            copy_to_ring(slots, &descs[..count as usize]);
+        }
+
+        // Wait for all earlier claims. Acquire, so that their writes are ordered before our
+        // release and thus visible to the kernel as well.
+        while inner.producer.load(Ordering::Acquire) != start {
+            core::hint::spin_loop();
+        }
+
+        inner
+            .producer
+            .store(start.wrapping_add(count), Ordering::Release);
+
+        count
+    }
+
+    /// Insert one descriptor, see [`SharedTx::insert_slice`].
+    pub fn insert_once(&self, desc: XdpDesc) -> bool {
+        self.insert_slice(core::slice::from_ref(&desc)) == 1
+    }
+
+    /// Return the difference between the published producer state and the kernel's consumer head.
+    pub fn pending(&self) -> u32 {
+        self.ring.count_pending()
+    }
+
+    /// Query if the transmit queue needs to be woken, see [`crate::RingTx::needs_wakeup`].
+    pub fn needs_wakeup(&self) -> bool {
+        self.ring.check_flags() & crate::RingTx::XDP_RING_NEED_WAKEUP != 0
+    }
+
+    /// Wake up the transmit queue, see [`crate::RingTx::try_wake`].
+    pub fn try_wake(&self) -> Result<WakeOutcome, Errno> {
+        match self.fd {
+            Some(fd) => wake_send(fd),
+            None => Ok(WakeOutcome::Woken),
+        }
+    }
+}
+
+impl Drop for SharedTx<'_> {
+    fn drop(&mut self) {
+        // Every claim was published before its call returned, catch up our own view of the ring.
+        let inner = &mut self.ring.inner;
+        inner.cached_producer = *self.claimed.get_mut();
+        debug_assert_eq!(
+            inner.cached_producer,
+            inner.producer.load(Ordering::Relaxed)
+        );
+        // As in `RingProd::count_free`, the cached consumer is kept one ring size ahead.
+        inner.cached_consumer = inner
+            .consumer
+            .load(Ordering::Acquire)
+            .wrapping_add(inner.size);
+    }
+}
+
+// Safety: the producer and consumer heads are only accessed atomically. The slots of the ring are
+// only written after being claimed by exactly one thread, through the atomic `claimed` head.
+unsafe impl Send for SharedTx<'_> {}
+unsafe impl Sync for SharedTx<'_> {}
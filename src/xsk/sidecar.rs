@@ -0,0 +1,201 @@
+use crate::xdp::XdpDesc;
+use crate::xsk::{BufIdx, FrameSidecar, Umem, UmemConfig, UmemPartition};
+
+impl<T> FrameSidecar<T> {
+    /// Create an entry for each frame of a `Umem`, initialized with the default value.
+    pub fn new(umem: &Umem) -> Self
+    where
+        T: Default,
+    {
+        Self::with(umem, |_| T::default())
+    }
+
+    /// Create an entry for each frame of a `Umem`, initialized by a function of its index.
+    pub fn with(umem: &Umem, init: impl FnMut(BufIdx) -> T) -> Self {
+        Self::with_frames(&umem.config, 0, umem.len_frames(), init)
+    }
+
+    /// Create an entry for each frame of a partition, initialized with the default value.
+    ///
+    /// Frames outside the partition have no entry.
+    pub fn with_partition(partition: &UmemPartition) -> Self
+    where
+        T: Default,
+    {
+        let range = partition.range();
+        Self::with_frames(&partition.config, range.start, range.end, |_| T::default())
+    }
+
+    fn with_frames(
+        config: &UmemConfig,
+        first: u32,
+        end: u32,
+        init: impl FnMut(BufIdx) -> T,
+    ) -> Self {
+        FrameSidecar {
+            config: config.clone(),
+            first,
+            entries: (first..end).map(BufIdx).map(init).collect(),
+        }
+    }
+
+    /// The number of frames with an entry.
+    pub fn len_frames(&self) -> u32 {
+        self.entries.len() as u32
+    }
+
+    /// Get the entry of a frame.
+    ///
+    /// Returns `None` if the frame has no entry.
+    pub fn get(&self, idx: BufIdx) -> Option<&T> {
+        let slot = idx.0.checked_sub(self.first)?;
+        self.entries.get(slot as usize)
+    }
+
+    /// Get the entry of a frame, mutably.
+    ///
+    /// Returns `None` if the frame has no entry.
+    pub fn get_mut(&mut self, idx: BufIdx) -> Option<&mut T> {
+        let slot = idx.0.checked_sub(self.first)?;
+        self.entries.get_mut(slot as usize)
+    }
+
+    /// Get the entry of the frame containing an address, such as from the completion ring.
+    ///
+    /// This resolves headroom and in-chunk offsets, as [`Umem::frame_index`].
+    pub fn get_addr(&self, addr: u64) -> Option<&T> {
+        self.get(self.index_of(addr)?)
+    }
+
+    /// Get the entry of the frame containing an address, mutably.
+    pub fn get_addr_mut(&mut self, addr: u64) -> Option<&mut T> {
+        self.get_mut(self.index_of(addr)?)
+    }
+
+    /// Get the entry of the frame of a descriptor, such as from the receive ring.
+    pub fn get_desc(&self, desc: &XdpDesc) -> Option<&T> {
+        self.get_addr(desc.addr)
+    }
+
+    /// Get the entry of the frame of a descriptor, mutably.
+    pub fn get_desc_mut(&mut self, desc: &XdpDesc) -> Option<&mut T> {
+        self.get_addr_mut(desc.addr)
+    }
+
+    /// Iterate over all frames and their entries.
+    pub fn iter(&self) -> impl Iterator<Item = (BufIdx, &T)> {
+        let first = self.first;
+        self.entries
+            .iter()
+            .enumerate()
+            .map(move |(slot, entry)| (BufIdx(first + slot as u32), entry))
+    }
+
+    /// Iterate over all frames and their entries, mutably.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (BufIdx, &mut T)> {
+        let first = self.first;
+        self.entries
+            .iter_mut()
+            .enumerate()
+            .map(move |(slot, entry)| (BufIdx(first + slot as u32), entry))
+    }
+
+    fn index_of(&self, addr: u64) -> Option<BufIdx> {
+        u32::try_from(self.config.frame_index(addr))
+            .ok()
+            .map(BufIdx)
+    }
+}
+
+impl<T> core::ops::Index<BufIdx> for FrameSidecar<T> {
+    type Output = T;
+
+    fn index(&self, idx: BufIdx) -> &T {
+        self.get(idx).expect("frame has no entry in the sidecar")
+    }
+}
+
+impl<T> core::ops::IndexMut<BufIdx> for FrameSidecar<T> {
+    fn index_mut(&mut self, idx: BufIdx) -> &mut T {
+        self.get_mut(idx)
+            .expect("frame has no entry in the sidecar")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xdp::XdpDesc;
+    use crate::xsk::{BufIdx, FrameSidecar, UmemConfig};
+
+    const FRAME: u64 = 4096;
+
+    /// Entries for the frames `4..8`, as for a partition, holding their own index.
+    fn partition(config: &UmemConfig) -> FrameSidecar<u32> {
+        FrameSidecar::with_frames(config, 4, 8, |idx| idx.0)
+    }
+
+    #[test]
+    fn first_and_last_frame() {
+        let mut sidecar = partition(&UmemConfig::default());
+        assert_eq!(sidecar.len_frames(), 4);
+
+        assert_eq!(sidecar.get(BufIdx(4)), Some(&4));
+        assert_eq!(sidecar.get(BufIdx(7)), Some(&7));
+        assert_eq!(sidecar.get_addr(4 * FRAME), Some(&4));
+        assert_eq!(sidecar.get_addr(8 * FRAME - 1), Some(&7));
+
+        *sidecar.get_mut(BufIdx(7)).unwrap() = 70;
+        assert_eq!(sidecar[BufIdx(7)], 70);
+
+        let indices: alloc::vec::Vec<_> = sidecar.iter().map(|(idx, _)| idx.0).collect();
+        assert_eq!(indices, [4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut sidecar = partition(&UmemConfig::default());
+
+        assert_eq!(sidecar.get(BufIdx(3)), None);
+        assert_eq!(sidecar.get(BufIdx(8)), None);
+        assert_eq!(sidecar.get(BufIdx(u32::MAX)), None);
+        assert_eq!(sidecar.get_mut(BufIdx(8)), None);
+        assert_eq!(sidecar.get_addr(4 * FRAME - 1), None);
+        assert_eq!(sidecar.get_addr(8 * FRAME), None);
+        assert_eq!(sidecar.get_addr(u64::MAX), None);
+    }
+
+    #[test]
+    #[should_panic = "frame has no entry"]
+    fn index_out_of_range() {
+        let sidecar = partition(&UmemConfig::default());
+        let _ = sidecar[BufIdx(8)];
+    }
+
+    #[test]
+    fn descriptor_with_offset() {
+        let config = UmemConfig::default();
+        let sidecar = partition(&config);
+
+        // The data of a received frame, after the kernel's headroom.
+        let desc = XdpDesc {
+            addr: 5 * FRAME + 256,
+            len: 60,
+            options: 0,
+        };
+        assert_eq!(sidecar.get_desc(&desc), Some(&5));
+
+        let config = UmemConfig {
+            flags: UmemConfig::XDP_UMEM_UNALIGNED_CHUNK_FLAG,
+            ..config
+        };
+        let sidecar = partition(&config);
+
+        // The entry of the chunk's base, even if the offset points into the next frame.
+        let desc = XdpDesc {
+            addr: UmemConfig::unaligned_addr(8 * FRAME - 64, 128),
+            len: 60,
+            options: 0,
+        };
+        assert_eq!(sidecar.get_desc(&desc), Some(&7));
+    }
+}
@@ -0,0 +1,223 @@
+use core::sync::atomic::Ordering;
+
+use crate::xdp::XdpDesc;
+use crate::xsk::{
+    BufIdx, ConfigError, ConfigField, FillManager, ReadComplete, ReadRx, RingCons, RingProd,
+    SetupError, SharedTx, Simulator, SocketConfig, UmemConfig, WriteFill, WriteTx, XskRing,
+};
+
+impl Simulator {
+    /// Create the rings with the sizes of a configuration, all of them empty.
+    ///
+    /// Fails with a [`SetupError::Config`] if either configuration does not pass its validation,
+    /// or if the socket configuration does not have both a receive and a transmit ring.
+    pub fn new(umem: &UmemConfig, socket: &SocketConfig) -> Result<Self, SetupError> {
+        umem.validate()?;
+        socket.validate()?;
+
+        let (rx_size, tx_size) = match (socket.rx_size, socket.tx_size) {
+            (Some(rx), Some(tx)) => (rx.get(), tx.get()),
+            (None, _) => return Err(Self::missing_ring(ConfigField::RxSize).into()),
+            (_, None) => return Err(Self::missing_ring(ConfigField::TxSize).into()),
+        };
+
+        let (fill, fill_kernel) = RingProd::anonymous::<u64>(umem.fill_size)?;
+        let (comp, comp_kernel) = RingCons::anonymous::<u64>(umem.complete_size)?;
+        let (rx, rx_kernel) = RingCons::anonymous::<XdpDesc>(rx_size)?;
+        let (tx, tx_kernel) = RingProd::anonymous::<XdpDesc>(tx_size)?;
+
+        Ok(Simulator {
+            fill,
+            fill_kernel,
+            comp,
+            comp_kernel,
+            rx,
+            rx_kernel,
+            tx,
+            tx_kernel,
+        })
+    }
+
+    fn missing_ring(field: ConfigField) -> ConfigError {
+        ConfigError::new(
+            field,
+            "the simulator needs both a receive and a transmit ring",
+        )
+    }
+
+    /// Prepare some buffers for the fill ring, see [`crate::DeviceQueue::fill`].
+    pub fn fill(&mut self, max: u32) -> WriteFill<'_> {
+        WriteFill::new(&mut self.fill, max)
+    }
+
+    /// Top up the fill ring, see [`crate::FillManager::refill`].
+    pub fn refill(
+        &mut self,
+        manager: &FillManager,
+        source: impl FnOnce(&mut WriteFill<'_>) -> u32,
+    ) -> u32 {
+        manager.refill_ring(&mut self.fill, source)
+    }
+
+    /// Reap some buffers from the completion ring, see [`crate::DeviceQueue::complete`].
+    pub fn complete(&mut self, n: u32) -> ReadComplete<'_> {
+        ReadComplete::new(&mut self.comp, n)
+    }
+
+    /// Move completed buffers back into the fill ring, see [`crate::DeviceQueue::recycle`].
+    pub fn recycle(&mut self, max: u32) -> u32 {
+        super::user::recycle(&mut self.comp, &mut self.fill, max)
+    }
+
+    /// Receive some buffers, see [`crate::RingRx::receive`].
+    pub fn receive(&mut self, n: u32) -> ReadRx<'_> {
+        ReadRx::new(&mut self.rx, n)
+    }
+
+    /// Transmit some buffers, see [`crate::RingTx::transmit`].
+    pub fn transmit(&mut self, n: u32) -> WriteTx<'_> {
+        WriteTx::new(&mut self.tx, n)
+    }
+
+    /// Share the transmit ring between threads, see [`crate::RingTx::share`].
+    ///
+    /// Waking the shared ring always succeeds, there is no socket.
+    pub fn share_tx(&mut self) -> SharedTx<'_> {
+        SharedTx::new(&mut self.tx, None)
+    }
+
+    /// As the kernel, consume the next committed address of the fill ring.
+    pub fn take_fill(&mut self) -> Option<u64> {
+        // Safety: the fill ring's entries are addresses.
+        unsafe { self.fill_kernel.sim_consume::<u64>() }
+    }
+
+    /// As the kernel, consume the next committed descriptor of the transmit ring.
+    pub fn take_tx(&mut self) -> Option<XdpDesc> {
+        // Safety: the transmit ring's entries are descriptors.
+        unsafe { self.tx_kernel.sim_consume::<XdpDesc>() }
+    }
+
+    /// As the kernel, produce an address in the completion ring.
+    ///
+    /// Returns false if the ring is full.
+    pub fn push_completion(&mut self, addr: u64) -> bool {
+        // Safety: the completion ring's entries are addresses.
+        unsafe { self.comp_kernel.sim_produce(addr) }
+    }
+
+    /// As the kernel, produce a descriptor in the receive ring.
+    ///
+    /// Returns false if the ring is full.
+    pub fn push_rx(&mut self, desc: XdpDesc) -> bool {
+        // Safety: the receive ring's entries are descriptors.
+        unsafe { self.rx_kernel.sim_produce(desc) }
+    }
+
+    /// As a kernel violating the ring protocol, overwrite the producer head of the receive ring.
+    ///
+    /// No descriptors are written. This is for testing how the user side copes with a corrupted
+    /// ring, for instance that the `ring-invariants` feature detects a producer that is more than
+    /// the ring size ahead of the consumer.
+    pub fn set_rx_producer(&mut self, producer: u32) {
+        self.rx_kernel.producer.store(producer, Ordering::Release);
+    }
+
+    /// The number of committed entries of the fill ring, not yet consumed by the kernel side.
+    pub fn pending_fill(&self) -> u32 {
+        self.fill_kernel.sim_len()
+    }
+
+    /// The number of committed entries of the transmit ring, not yet consumed by the kernel side.
+    pub fn pending_tx(&self) -> u32 {
+        self.tx_kernel.sim_len()
+    }
+
+    /// As the kernel, transmit descriptors back to the receive ring.
+    ///
+    /// Each transmitted descriptor is received into the next frame of the fill ring, with the
+    /// same length and options, and its address is completed. Stops when the transmit or fill
+    /// ring is empty, or the receive or completion ring is full. Returns the number of
+    /// descriptors that were looped back.
+    ///
+    /// Only descriptors are moved, the contents of the frames are not copied.
+    pub fn loopback(&mut self) -> u32 {
+        let mut n = 0;
+
+        while self.pending_tx() > 0
+            && self.pending_fill() > 0
+            && self.rx_kernel.sim_free() > 0
+            && self.comp_kernel.sim_free() > 0
+        {
+            let (tx, fill) = match (self.take_tx(), self.take_fill()) {
+                (Some(tx), Some(fill)) => (tx, fill),
+                _ => unreachable!("checked to be non-empty"),
+            };
+
+            let received = self.push_rx(XdpDesc { addr: fill, ..tx });
+            let completed = self.push_completion(tx.addr);
+            debug_assert!(received && completed);
+            n += 1;
+        }
+
+        n
+    }
+}
+
+impl XskRing {
+    /// The number of entries between the consumer and producer, as seen by the kernel side.
+    fn sim_len(&self) -> u32 {
+        let producer = self.producer.load(Ordering::Acquire);
+        let consumer = self.consumer.load(Ordering::Acquire);
+        producer.wrapping_sub(consumer)
+    }
+
+    /// The number of entries the kernel side can produce.
+    fn sim_free(&self) -> u32 {
+        self.size - self.sim_len()
+    }
+
+    /// Consume one entry as the consumer side, the user side being the producer.
+    ///
+    /// # Safety
+    ///
+    /// The type `T` must be the type of entries of this ring.
+    unsafe fn sim_consume<T: Copy>(&self) -> Option<T> {
+        let producer = self.producer.load(Ordering::Acquire);
+        let consumer = self.consumer.load(Ordering::Relaxed);
+
+        if producer == consumer {
+            return None;
+        }
+
+        let [slot, _] = unsafe { self.slots::<T>(BufIdx(consumer), 1) };
+        // Safety: the entry is within the ring and was written before the producer was released.
+        let entry = unsafe { *slot.cast::<T>().as_ptr() };
+        self.consumer
+            .store(consumer.wrapping_add(1), Ordering::Release);
+
+        Some(entry)
+    }
+
+    /// Produce one entry as the producer side, the user side being the consumer.
+    ///
+    /// # Safety
+    ///
+    /// The type `T` must be the type of entries of this ring.
+    unsafe fn sim_produce<T>(&self, entry: T) -> bool {
+        let producer = self.producer.load(Ordering::Relaxed);
+        let consumer = self.consumer.load(Ordering::Acquire);
+
+        if producer.wrapping_sub(consumer) >= self.size {
+            return false;
+        }
+
+        let [slot, _] = unsafe { self.slots::<T>(BufIdx(producer), 1) };
+        // Safety: the entry is within the ring and no longer read by the consumer side.
+        unsafe { slot.cast::<T>().as_ptr().write(entry) };
+        self.producer
+            .store(producer.wrapping_add(1), Ordering::Release);
+
+        true
+    }
+}
@@ -1,6 +1,7 @@
 use alloc::sync::Arc;
 
-use crate::xsk::{IfInfo, Socket, SocketFd, Umem};
+use crate::mmap::Mmap;
+use crate::xsk::{IfInfo, MappedRings, Socket, SocketFd, Umem};
 use crate::{Errno, LastErrno};
 
 impl Socket {
@@ -10,15 +11,30 @@ impl Socket {
     /// Create a new socket for a given interface.
     pub fn new(interface: &IfInfo) -> Result<Self, Errno> {
         let fd = Arc::new(SocketFd::new()?);
-        Self::with_xdp_socket(interface, fd)
+        Self::with_xdp_socket(interface, fd, None, false)
     }
 
     /// Create a socket using the FD of the `umem`.
+    ///
+    /// Fails with `EINVAL` for the `Umem` of a [`BorrowedUmem`], use [`BorrowedUmem::socket`]
+    /// instead.
+    ///
+    /// [`BorrowedUmem`]: crate::BorrowedUmem
+    /// [`BorrowedUmem::socket`]: crate::BorrowedUmem::socket
     pub fn with_shared(interface: &IfInfo, umem: &Umem) -> Result<Self, Errno> {
-        Self::with_xdp_socket(interface, umem.fd.clone())
+        if umem.borrowed {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Self::with_xdp_socket(interface, umem.fd.clone(), umem.area.clone(), false)
     }
 
-    fn with_xdp_socket(interface: &IfInfo, fd: Arc<SocketFd>) -> Result<Self, Errno> {
+    pub(crate) fn with_xdp_socket(
+        interface: &IfInfo,
+        fd: Arc<SocketFd>,
+        area: Option<Arc<Mmap>>,
+        borrowed: bool,
+    ) -> Result<Self, Errno> {
         let mut info = Arc::new(*interface);
 
         let mut netnscookie: u64 = 0;
@@ -42,7 +58,12 @@ impl Socket {
         // Won't reallocate in practice.
         Arc::make_mut(&mut info).ctx.netnscookie = netnscookie;
 
-        Ok(Socket { fd, info })
+        Ok(Socket {
+            fd,
+            info,
+            area,
+            borrowed,
+        })
     }
 }
 
@@ -52,6 +73,6 @@ impl SocketFd {
         if fd < 0 {
             return Err(LastErrno)?;
         }
-        Ok(SocketFd(fd))
+        Ok(SocketFd(fd, MappedRings::default()))
     }
 }
@@ -1,12 +1,16 @@
+use core::ops::Range;
 use core::ptr::NonNull;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use alloc::collections::BTreeSet;
 use alloc::sync::Arc;
 
+use crate::mmap::{Mmap, PageSize};
 use crate::xdp::{SockAddrXdp, XdpDesc, XdpStatistics, XdpStatisticsV2, XdpUmemReg};
 use crate::xsk::{
-    ptr_len, BufIdx, DeviceControl, DeviceQueue, DeviceRings, IfCtx, RingCons, RingProd, RingRx,
-    RingTx, Socket, SocketConfig, SocketFd, SocketMmapOffsets, Umem, UmemChunk, UmemConfig, User,
+    ptr_len, BufIdx, ConfigError, ConfigField, DeviceControl, DeviceQueue, DeviceRings, IfCtx,
+    IfInfo, RingCons, RingProd, RingRx, RingTx, SetupError, Socket, SocketConfig, SocketFd,
+    SocketMmapOffsets, Umem, UmemChunk, UmemConfig, User,
 };
 use crate::{Errno, LastErrno};
 
@@ -56,23 +60,255 @@ impl Umem {
     ///
     /// The area must be page aligned and not exceed i64::MAX in length (on future systems where
     /// you could).
+    ///
+    /// Fails with `EINVAL`, before any system call, if the configuration or the length of the area
+    /// does not pass [`UmemConfig::validate_area`]. [`Umem::try_new`] reports the reason instead.
     pub unsafe fn new(config: UmemConfig, area: NonNull<[u8]>) -> Result<Umem, Errno> {
-        fn is_page_aligned(area: NonNull<[u8]>) -> bool {
-            let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        Ok(Self::try_new(config, area)?)
+    }
+
+    /// Create a new Umem ring, reporting an invalid configuration as a [`SetupError::Config`].
+    ///
+    /// # Safety
+    ///
+    /// See [`Umem::new`].
+    pub unsafe fn try_new(config: UmemConfig, area: NonNull<[u8]>) -> Result<Umem, SetupError> {
+        Self::with_area(config, area, None)
+    }
+
+    /// Create a new Umem ring, allocating and owning its memory area.
+    ///
+    /// The area is an anonymous memory mapping large enough to hold `frame_count` frames of the
+    /// configured size. It is unmapped only after the `Umem` and every [`Socket`],
+    /// [`DeviceQueue`], [`User`], [`RingRx`] and [`RingTx`] derived from it have been dropped.
+    ///
+    /// Fails with a [`SetupError::Config`] if the configuration does not pass its validation, the
+    /// frame count is zero, or the area would not fit into the address space.
+    pub fn allocate(config: UmemConfig, frame_count: u32) -> Result<Umem, SetupError> {
+        let len = Self::allocation_len(&config, frame_count)?;
+        let mmap = Mmap::anonymous(len)?;
+        // Safety: the mapping was just created for `len` bytes.
+        unsafe { Self::with_mmap(config, len, mmap) }
+    }
+
+    /// Create a new Umem ring, allocating its memory area from pages of the preferred size.
+    ///
+    /// This works like [`Umem::allocate`] but tries to back the area with huge pages, reducing
+    /// the pressure on the TLB. The area is rounded up to a multiple of the page size. If the
+    /// system has not reserved sufficient huge pages of the requested size (see
+    /// `/proc/sys/vm/nr_hugepages` or the `hugepages=` boot parameter) then the allocation falls
+    /// back to the base page size. Query [`Umem::page_size`] for the size that was actually used.
+    pub fn allocate_with_pages(
+        config: UmemConfig,
+        frame_count: u32,
+        page_size: PageSize,
+    ) -> Result<Umem, SetupError> {
+        let len = Self::allocation_len(&config, frame_count)?;
+
+        let mmap = match Mmap::anonymous_with_pages(len, page_size) {
+            Ok(mmap) => mmap,
+            // No pages reserved, or the page size is not supported by the kernel at all.
+            Err(err) if matches!(err.get_raw(), libc::ENOMEM | libc::EINVAL) => {
+                Mmap::anonymous(len)?
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        // Safety: the mapping was just created for at least `len` bytes.
+        unsafe { Self::with_mmap(config, len, mmap) }
+    }
+
+    /// Create a new Umem ring, allocating its memory area in a memory file.
+    ///
+    /// This works like [`Umem::allocate`] but the area is a shared mapping of a file created with
+    /// `memfd_create`. Pass the file descriptor from [`Umem::as_raw_memfd`] to another process,
+    /// for instance over a unix socket, which can then map the same frames with
+    /// [`Umem::attach_memfd`]. The file holds an additional page after the frames, recording the
+    /// frame size and count for the attaching side.
+    pub fn allocate_memfd(config: UmemConfig, frame_count: u32) -> Result<Umem, SetupError> {
+        let len = Self::allocation_len(&config, frame_count)?;
+        let geometry = MemfdGeometry::new(&config, frame_count);
+        // Never fails, checked for every allocation.
+        let (offset, file_len) = MemfdGeometry::layout(len).ok_or(Errno(libc::EINVAL))?;
+
+        let mmap = Mmap::create_memfd(file_len)?;
+        // Safety: the mapping was just created for `file_len` bytes, the record is within them.
+        // The page of the record is not part of the area, and no one else has mapped it yet.
+        unsafe {
+            let record = mmap.as_ptr().cast::<u8>().as_ptr().add(offset);
+            core::ptr::write_volatile(record.cast::<MemfdGeometry>(), geometry);
+        }
+
+        // Safety: the mapping was just created for more than `len` bytes.
+        unsafe { Self::with_mmap(config, len, mmap) }
+    }
+
+    /// Create a new Umem ring, mapping the frames of an existing memory file.
+    ///
+    /// The file is typically created by another process with [`Umem::allocate_memfd`]. The file
+    /// descriptor is duplicated, the caller retains ownership of `fd`. Fails with a
+    /// [`SetupError::Config`] for the area unless the file was created for exactly `frame_count`
+    /// frames of the configured frame size, i.e. both processes must agree on the geometry of the
+    /// area. The size of the file must also be sealed, as is done by [`Umem::allocate_memfd`], see
+    /// `F_SEAL_SHRINK` in `memfd_create(2)`.
+    ///
+    /// # Safety
+    ///
+    /// The `fd` must be an open file descriptor for the duration of the call. The memory of the
+    /// file is shared with the other processes mapping it. The caller must ensure that they
+    /// coordinate their accesses to the frames, as is the case for the kernel and the rings.
+    pub unsafe fn attach_memfd(
+        config: UmemConfig,
+        fd: libc::c_int,
+        frame_count: u32,
+    ) -> Result<Umem, SetupError> {
+        let len = Self::allocation_len(&config, frame_count)?;
+        let geometry = MemfdGeometry::new(&config, frame_count);
+        // Never fails, checked for every allocation.
+        let (offset, file_len) = MemfdGeometry::layout(len).ok_or(Errno(libc::EINVAL))?;
+
+        let mmap = Mmap::attach_memfd(fd)?;
+
+        if ptr_len(mmap.as_ptr().as_ptr()) != file_len {
+            return Err(MemfdGeometry::mismatch().into());
+        }
+
+        // Safety: the mapping is valid for its complete length, which we just checked.
+        let recorded = unsafe {
+            let record = mmap.as_ptr().cast::<u8>().as_ptr().add(offset);
+            core::ptr::read_volatile(record.cast::<MemfdGeometry>())
+        };
+
+        if recorded != geometry {
+            return Err(MemfdGeometry::mismatch().into());
+        }
+
+        // Safety: the mapping is valid for more than `len` bytes, which we just checked.
+        unsafe { Self::with_mmap(config, len, mmap) }
+    }
+
+    /// Create a new Umem ring, allocating its memory area on a specific NUMA node.
+    ///
+    /// This works like [`Umem::allocate`] but binds the memory of the area to `node` with `mbind`,
+    /// before any of its pages are faulted in. Fails if the node does not exist or has no memory.
+    pub fn allocate_on_node(
+        config: UmemConfig,
+        frame_count: u32,
+        node: u32,
+    ) -> Result<Umem, SetupError> {
+        let len = Self::allocation_len(&config, frame_count)?;
+        let mut mmap = Mmap::anonymous(len)?;
+        mmap.bind_node(node)?;
+        // Safety: the mapping was just created for `len` bytes.
+        unsafe { Self::with_mmap(config, len, mmap) }
+    }
+
+    /// Create a new Umem ring, allocating its memory area on the NUMA node of a device.
+    ///
+    /// Frames are accessed by the device with DMA, placing them on the node the device is attached
+    /// to avoids crossing the interconnect between sockets. The node is found with
+    /// [`IfInfo::numa_node`]. If the device is not associated with a node then this is the same as
+    /// [`Umem::allocate`]. Query [`Umem::numa_node`] for the node that was actually used.
+    pub fn allocate_near(
+        config: UmemConfig,
+        frame_count: u32,
+        interface: &IfInfo,
+    ) -> Result<Umem, SetupError> {
+        match interface.numa_node()? {
+            Some(node) => Self::allocate_on_node(config, frame_count, node),
+            None => Self::allocate(config, frame_count),
+        }
+    }
+
+    /// The NUMA node the memory area is bound to.
+    ///
+    /// Only a `Umem` created by [`Umem::allocate_on_node`] or [`Umem::allocate_near`] is bound to
+    /// a node, for all others this returns `None`.
+    pub fn numa_node(&self) -> Option<u32> {
+        self.area.as_ref().and_then(|mmap| mmap.node())
+    }
+
+    /// Get the raw file descriptor of the memory file backing the area, if any.
+    ///
+    /// Only a `Umem` created by [`Umem::allocate_memfd`] or [`Umem::attach_memfd`] has such a file.
+    /// The descriptor remains owned by the `Umem`, do not close it.
+    pub fn as_raw_memfd(&self) -> Option<libc::c_int> {
+        self.area.as_ref().and_then(|mmap| mmap.memfd())
+    }
+
+    /// The size of pages that back the memory area.
+    ///
+    /// For areas passed to [`Umem::new`] the library can not know how the memory was allocated
+    /// and this always returns [`PageSize::Base`].
+    pub fn page_size(&self) -> PageSize {
+        self.area
+            .as_ref()
+            .map_or(PageSize::Base, |mmap| mmap.page_size())
+    }
+
+    fn allocation_len(config: &UmemConfig, frame_count: u32) -> Result<usize, ConfigError> {
+        config.validate()?;
+
+        if frame_count == 0 {
+            return Err(ConfigError::new(
+                ConfigField::Area,
+                "must hold at least one frame",
+            ));
+        }
+
+        let len = u64::from(config.frame_size) * u64::from(frame_count);
+        usize::try_from(len)
+            .ok()
+            .and_then(|len| MemfdGeometry::layout(len).map(|_| len))
+            .ok_or(ConfigError::new(
+                ConfigField::Area,
+                "must fit into the address space",
+            ))
+    }
+
+    /// # Safety
+    ///
+    /// The mapping must be valid for at least `len` bytes.
+    unsafe fn with_mmap(config: UmemConfig, len: usize, mmap: Mmap) -> Result<Umem, SetupError> {
+        let mmap = Arc::new(mmap);
+        // The mapping may be larger, rounded up to a full page. Register exactly the frames.
+        let area = core::ptr::slice_from_raw_parts_mut(mmap.as_ptr().cast::<u8>().as_ptr(), len);
+        let area = unsafe { NonNull::new_unchecked(area) };
+
+        // Safety: the mapping is page aligned, valid for `len` bytes, and lives as long as we
+        // keep a reference to it in the `Umem` and all derived objects.
+        unsafe { Self::with_area(config, area, Some(mmap)) }
+    }
+
+    /// Construct the `Umem`, optionally taking ownership of the area.
+    ///
+    /// # Safety
+    ///
+    /// See [`Umem::new`]. If `owner` is provided it must keep `area` valid.
+    pub(crate) unsafe fn with_area(
+        config: UmemConfig,
+        area: NonNull<[u8]>,
+        owner: Option<Arc<Mmap>>,
+    ) -> Result<Umem, SetupError> {
+        fn is_page_aligned(area: NonNull<[u8]>, page_size: PageSize) -> bool {
+            let page_size = page_size.bytes();
             // TODO: use `addr()` as we don't need to expose the pointer here. Just the address as
             // an integer and no provenance-preserving cast intended.
             (area.as_ptr() as *mut u8 as usize & (page_size - 1)) == 0
         }
 
-        assert!(config.frame_size > 0, "Invalid frame size");
+        let area_size = ptr_len(area.as_ptr());
+        config.validate_area(area_size)?;
 
+        // Huge pages are always aligned to their own, larger, size.
+        let page_size = owner
+            .as_ref()
+            .map_or(PageSize::Base, |mmap| mmap.page_size());
         assert!(
-            is_page_aligned(area),
+            is_page_aligned(area, page_size),
             "UB: Bad mmap area provided, but caller is responsible for its soundness."
         );
 
-        let area_size = ptr_len(area.as_ptr());
-
         assert!(
             u64::try_from(area_size).is_ok(),
             "Unhandled address space calculation"
@@ -91,6 +327,8 @@ impl Umem {
             fd: Arc::new(SocketFd::new()?),
             umem_area: area,
             devices,
+            area: owner,
+            borrowed: false,
         };
 
         Self::configure(&umem)?;
@@ -130,11 +368,111 @@ impl Umem {
         Some(UmemChunk { addr, offset })
     }
 
+    /// Get the chunk of `len` bytes starting at an arbitrary offset, if it is in-bounds.
+    ///
+    /// This is intended for the unaligned chunk mode (see
+    /// [`UmemConfig::XDP_UMEM_UNALIGNED_CHUNK_FLAG`]) where packets of different sizes can be
+    /// packed densely into the area. The length must not exceed the configured frame size, which
+    /// the kernel enforces for every descriptor.
+    ///
+    /// In the default, aligned mode the kernel masks every address to the start of its frame. The
+    /// chunk is then only returned if it does not cross a frame boundary.
+    pub fn chunk_at_offset(&self, offset: u64, len: u32) -> Option<UmemChunk> {
+        let pitch: u32 = self.config.frame_size;
+        let area_size = ptr_len(self.umem_area.as_ptr()) as u64;
+
+        if len > pitch || area_size.checked_sub(u64::from(len)) < Some(offset) {
+            return None;
+        }
+
+        if !self.config.is_unaligned() {
+            let in_frame = offset % u64::from(pitch);
+            if in_frame + u64::from(len) > u64::from(pitch) {
+                return None;
+            }
+        }
+
+        // Safety: we have just checked `[offset..offset+len) < area_size`, see `Umem::frame`.
+        let base = unsafe { self.umem_area.cast::<u8>().as_ptr().offset(offset as isize) };
+        debug_assert!(!base.is_null(), "UB: offsetting area within produced NULL");
+        let slice = core::ptr::slice_from_raw_parts_mut(base, len as usize);
+        let addr = unsafe { NonNull::new_unchecked(slice) };
+        Some(UmemChunk { addr, offset })
+    }
+
+    /// Resolve the address of a descriptor to the absolute offset of its data.
+    ///
+    /// In unaligned chunk mode, descriptors returned by the kernel encode an additional offset
+    /// into the chunk in the upper 16 bits of the address. This offset is added to the base. In
+    /// the aligned mode the address is returned unchanged.
+    pub fn resolve_addr(&self, addr: u64) -> u64 {
+        self.config.resolve_addr(addr)
+    }
+
+    /// Find the index of the frame containing a kernel-reported address.
+    ///
+    /// The address can be any address of a descriptor or completion, including any headroom and
+    /// offset of the data into the frame. In unaligned chunk mode, this is the frame containing
+    /// the start of the chunk. Returns `None` if the address is not within the area.
+    pub fn frame_index(&self, addr: u64) -> Option<BufIdx> {
+        let area_size = ptr_len(self.umem_area.as_ptr()) as u64;
+        self.config.frame_index_in(area_size, addr).map(BufIdx)
+    }
+
+    /// Resolve a kernel-reported address to its data, up to the end of the containing chunk.
+    ///
+    /// The address is resolved as in [`Umem::resolve_addr`]. The returned chunk starts at the
+    /// data, its `offset` is the absolute offset of the data in the `Umem`, and it extends to the
+    /// end of the chunk containing it. In the aligned mode that is the end of the frame, in
+    /// unaligned chunk mode it is one frame size past the base address of the chunk. The chunk is
+    /// cut off at the end of the area.
+    ///
+    /// Returns `None` if the address is not within the area.
+    pub fn chunk_at(&self, addr: u64) -> Option<UmemChunk> {
+        let area_size = ptr_len(self.umem_area.as_ptr()) as u64;
+        let range = self.config.chunk_range(area_size, addr)?;
+        // Safety: the range is within the area, checked against its size.
+        Some(unsafe { self.chunk_in(range) })
+    }
+
+    /// Resolve a descriptor to exactly the bytes of its data.
+    ///
+    /// Same as [`Umem::chunk_at`] but the chunk has the length of the descriptor. Returns `None`
+    /// if the address is not within the area or the length exceeds the chunk.
+    pub fn chunk_of(&self, desc: &XdpDesc) -> Option<UmemChunk> {
+        let area_size = ptr_len(self.umem_area.as_ptr()) as u64;
+        let range = self.config.desc_range(area_size, desc)?;
+        // Safety: the range is within the area, checked against its size.
+        Some(unsafe { self.chunk_in(range) })
+    }
+
     /// Count the number of available data frames.
     pub fn len_frames(&self) -> u32 {
         let area_size = ptr_len(self.umem_area.as_ptr()) as u64;
-        let count = area_size / u64::from(self.config.frame_size);
-        u32::try_from(count).unwrap_or(u32::MAX)
+        self.config.frame_count(area_size)
+    }
+
+    /// The chunk of a range of offsets.
+    ///
+    /// # Safety
+    ///
+    /// The range must be within the area, see [`Umem::frame`].
+    unsafe fn chunk_in(&self, range: Range<u64>) -> UmemChunk {
+        debug_assert!(range.end <= ptr_len(self.umem_area.as_ptr()) as u64);
+        let base = unsafe {
+            self.umem_area
+                .cast::<u8>()
+                .as_ptr()
+                .offset(range.start as isize)
+        };
+        debug_assert!(!base.is_null(), "UB: offsetting area within produced NULL");
+        let len = (range.end - range.start) as usize;
+        let slice = core::ptr::slice_from_raw_parts_mut(base, len);
+        let addr = unsafe { NonNull::new_unchecked(slice) };
+        UmemChunk {
+            addr,
+            offset: range.start,
+        }
     }
 
     fn configure(this: &Umem) -> Result<(), Errno> {
@@ -144,9 +482,13 @@ impl Umem {
             chunk_size: this.config.frame_size,
             headroom: this.config.headroom,
             flags: this.config.flags,
-            ..XdpUmemReg::default()
+            tx_metadata_len: this.config.tx_metadata_len,
         };
 
+        if mr.tx_metadata_len != 0 {
+            mr.flags |= UmemConfig::XDP_UMEM_TX_METADATA_LEN;
+        }
+
         let optlen = core::mem::size_of_val(&mr) as libc::socklen_t;
         let err = unsafe {
             libc::setsockopt(
@@ -173,7 +515,18 @@ impl Umem {
     /// user-space portions of each queue is active at a time. The kernel won't care about your
     /// broken code and race conditions writing to the same queue concurrently. It's an SPSC.
     /// Probably only the first call for each interface succeeds.
+    ///
+    /// Fails with `EINVAL` for a `Umem` or socket of a [`crate::BorrowedUmem`], use
+    /// [`crate::BorrowedUmem::fq_cq`] instead.
     pub fn fq_cq(&self, interface: &Socket) -> Result<DeviceQueue, Errno> {
+        if self.borrowed || interface.borrowed {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        self.device_queue(interface)
+    }
+
+    pub(crate) fn device_queue(&self, interface: &Socket) -> Result<DeviceQueue, Errno> {
         if !self.devices.insert(interface.info.ctx) {
             // We know this will just yield `-EBUSY` anyways.
             return Err(Errno(libc::EINVAL));
@@ -205,6 +558,8 @@ impl Umem {
             socket: Socket {
                 info: interface.info.clone(),
                 fd: interface.fd.clone(),
+                area: self.area.clone(),
+                borrowed: self.borrowed,
             },
             devices: self.devices.clone(),
         };
@@ -220,7 +575,31 @@ impl Umem {
     ///
     /// Note: if the underlying socket is shared then this will also bind other objects that share
     /// the underlying socket file descriptor, this is intended.
+    ///
+    /// Fails with `EINVAL` for a `Umem` or socket of a [`crate::BorrowedUmem`], use
+    /// [`crate::BorrowedUmem::rx_tx`] instead. Also fails with `EINVAL`, before any system call, if
+    /// the configuration does not pass [`SocketConfig::validate`], [`Umem::try_rx_tx`] reports the
+    /// reason instead.
     pub fn rx_tx(&self, interface: &Socket, config: &SocketConfig) -> Result<User, Errno> {
+        Ok(self.try_rx_tx(interface, config)?)
+    }
+
+    /// Configure the device address for a socket, reporting an invalid configuration as a
+    /// [`SetupError::Config`]. See [`Umem::rx_tx`].
+    pub fn try_rx_tx(&self, interface: &Socket, config: &SocketConfig) -> Result<User, SetupError> {
+        if self.borrowed || interface.borrowed {
+            return Err(Errno(libc::EINVAL).into());
+        }
+
+        self.user(interface, config)
+    }
+
+    pub(crate) fn user(
+        &self,
+        interface: &Socket,
+        config: &SocketConfig,
+    ) -> Result<User, SetupError> {
+        config.validate()?;
         let sock = &*interface.fd;
         Self::configure_rt(sock, config)?;
         let map = SocketMmapOffsets::new(sock)?;
@@ -229,6 +608,8 @@ impl Umem {
             socket: Socket {
                 info: interface.info.clone(),
                 fd: interface.fd.clone(),
+                area: self.area.clone(),
+                borrowed: self.borrowed,
             },
             config: Arc::new(config.clone()),
             map,
@@ -264,11 +645,18 @@ impl Umem {
     /// | |- fd7: socket +rx/tx on ifq1 DeviceQueue::bind(fd6, fd7)
     /// | |- …
     /// ```
+    ///
+    /// Fails with `EINVAL` for a `Umem` or socket of a [`crate::BorrowedUmem`], use
+    /// [`crate::BorrowedUmem::bind`] instead.
     pub fn bind(&self, interface: &User) -> Result<(), Errno> {
+        if self.borrowed || interface.socket.borrowed {
+            return Err(Errno(libc::EINVAL));
+        }
+
         Self::bind_at(interface, &self.fd)
     }
 
-    fn bind_at(interface: &User, umem_sock: &SocketFd) -> Result<(), Errno> {
+    pub(crate) fn bind_at(interface: &User, umem_sock: &SocketFd) -> Result<(), Errno> {
         let mut sxdp = SockAddrXdp {
             ifindex: interface.socket.info.ctx.ifindex,
             queue_id: interface.socket.info.ctx.queue_id,
@@ -386,7 +774,14 @@ impl DeviceQueue {
     }
 
     /// Bind the socket to a device queue, activate rx/tx queues.
+    ///
+    /// Fails with `EINVAL` for a queue or socket of a [`crate::BorrowedUmem`], use
+    /// [`crate::Bound::bind`] instead.
     pub fn bind(&self, interface: &User) -> Result<(), Errno> {
+        if self.socket.borrowed || interface.socket.borrowed {
+            return Err(Errno(libc::EINVAL));
+        }
+
         Umem::bind_at(interface, &self.socket.fd)
     }
 }
@@ -407,14 +802,29 @@ impl User {
     ///
     /// Fails if you did not pass any size for `rx_size` in the configuration, which should be somewhat obvious.
     ///
-    /// FIXME: we allow mapping the ring more than once. Not a memory safety problem afaik, but a
-    /// correctness problem.
+    /// Fails with `EBUSY` if the RX ring of the socket is already mapped. It can be mapped again
+    /// once the previous handle was dropped.
+    ///
+    /// Fails with `EINVAL` for a socket of a [`crate::BorrowedUmem`], use
+    /// [`crate::Bound::map_rx`] instead.
     pub fn map_rx(&self) -> Result<RingRx, Errno> {
+        if self.socket.borrowed {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        self.rx_ring()
+    }
+
+    pub(crate) fn rx_ring(&self) -> Result<RingRx, Errno> {
         let rx_size = self.config.rx_size.ok_or(Errno(-libc::EINVAL))?.get();
-        let ring = unsafe { RingCons::rx(&self.socket.fd, &self.map, rx_size) }?;
+        let mapped = &self.socket.fd.1.rx;
+        claim_ring(mapped)?;
+        let ring = unsafe { RingCons::rx(&self.socket.fd, &self.map, rx_size) }
+            .map_err(|err| release_ring(mapped, err))?;
         Ok(RingRx {
             fd: self.socket.fd.clone(),
             ring,
+            area: self.socket.area.clone(),
         })
     }
 
@@ -422,18 +832,164 @@ impl User {
     ///
     /// Fails if you did not pass any size for `tx_size` in the configuration, which should be somewhat obvious.
     ///
-    /// FIXME: we allow mapping the ring more than once. Not a memory safety problem afaik, but a
-    /// correctness problem.
+    /// Fails with `EBUSY` if the TX ring of the socket is already mapped. It can be mapped again
+    /// once the previous handle was dropped.
+    ///
+    /// Fails with `EINVAL` for a socket of a [`crate::BorrowedUmem`], use
+    /// [`crate::Bound::map_tx`] instead.
     pub fn map_tx(&self) -> Result<RingTx, Errno> {
+        if self.socket.borrowed {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        self.tx_ring()
+    }
+
+    pub(crate) fn tx_ring(&self) -> Result<RingTx, Errno> {
         let tx_size = self.config.tx_size.ok_or(Errno(-libc::EINVAL))?.get();
-        let ring = unsafe { RingProd::tx(&self.socket.fd, &self.map, tx_size) }?;
+        let mapped = &self.socket.fd.1.tx;
+        claim_ring(mapped)?;
+        let ring = unsafe { RingProd::tx(&self.socket.fd, &self.map, tx_size) }
+            .map_err(|err| release_ring(mapped, err))?;
         Ok(RingTx {
             fd: self.socket.fd.clone(),
             ring,
+            area: self.socket.area.clone(),
         })
     }
 }
 
+/// Mark a ring of a socket as mapped, failing with `EBUSY` if it already is.
+fn claim_ring(mapped: &AtomicBool) -> Result<(), Errno> {
+    if mapped.swap(true, Ordering::AcqRel) {
+        return Err(Errno(libc::EBUSY));
+    }
+
+    Ok(())
+}
+
+/// Undo a claim after the ring could not be mapped, passing on the error.
+fn release_ring(mapped: &AtomicBool, err: Errno) -> Errno {
+    mapped.store(false, Ordering::Release);
+    err
+}
+
+impl UmemConfig {
+    /// Flag-bit for [`UmemConfig::flags`] that enables unaligned chunk mode.
+    ///
+    /// In this mode, the kernel does not mask descriptor addresses to the start of a frame.
+    /// Instead, packet buffers can start at any offset and have any size up to the frame size.
+    /// The frame size then also needs not be a power-of-two. Addresses returned by the kernel
+    /// encode an offset into the chunk in their upper bits, see
+    /// [`UmemConfig::unaligned_addr`].
+    pub const XDP_UMEM_UNALIGNED_CHUNK_FLAG: u32 = 1 << 0;
+    /// Flag-bit for [`UmemConfig::flags`] to compute requested checksums in software.
+    ///
+    /// Only affects copy-mode, for testing TX metadata requests.
+    pub const XDP_UMEM_TX_SW_CSUM: u32 = 1 << 1;
+    /// Flag-bit for [`UmemConfig::flags`] that the `tx_metadata_len` is valid.
+    ///
+    /// Generally, this flag need not be passed directly. Instead, it is set by the library when
+    /// [`UmemConfig::tx_metadata_len`] is non-zero.
+    pub const XDP_UMEM_TX_METADATA_LEN: u32 = 1 << 2;
+
+    /// The bit position of the in-chunk offset in an unaligned descriptor address.
+    pub const XSK_UNALIGNED_BUF_OFFSET_SHIFT: u32 = 48;
+    /// The mask of the base address bits in an unaligned descriptor address.
+    pub const XSK_UNALIGNED_BUF_ADDR_MASK: u64 = (1 << Self::XSK_UNALIGNED_BUF_OFFSET_SHIFT) - 1;
+
+    /// Check if the configuration enables unaligned chunk mode.
+    pub fn is_unaligned(&self) -> bool {
+        self.flags & Self::XDP_UMEM_UNALIGNED_CHUNK_FLAG != 0
+    }
+
+    /// Encode a descriptor address for unaligned chunk mode.
+    ///
+    /// The base is the offset of the chunk in the Umem, while `offset` is an additional offset
+    /// into the chunk which the kernel adds to the base. Bits of the base above the address mask
+    /// are discarded.
+    pub fn unaligned_addr(base: u64, offset: u16) -> u64 {
+        (base & Self::XSK_UNALIGNED_BUF_ADDR_MASK)
+            | (u64::from(offset) << Self::XSK_UNALIGNED_BUF_OFFSET_SHIFT)
+    }
+
+    /// Split a descriptor address of unaligned chunk mode into its base and in-chunk offset.
+    pub fn split_unaligned_addr(addr: u64) -> (u64, u16) {
+        let base = addr & Self::XSK_UNALIGNED_BUF_ADDR_MASK;
+        let offset = (addr >> Self::XSK_UNALIGNED_BUF_OFFSET_SHIFT) as u16;
+        (base, offset)
+    }
+
+    /// Resolve an address to the absolute offset of the data, see [`Umem::resolve_addr`].
+    pub(crate) fn resolve_addr(&self, addr: u64) -> u64 {
+        if self.is_unaligned() {
+            let (base, offset) = Self::split_unaligned_addr(addr);
+            base + u64::from(offset)
+        } else {
+            addr
+        }
+    }
+
+    /// The (unchecked) index of the frame containing an address, see [`Umem::frame_index`].
+    pub(crate) fn frame_index(&self, addr: u64) -> u64 {
+        let start = if self.is_unaligned() {
+            Self::split_unaligned_addr(addr).0
+        } else {
+            addr
+        };
+
+        start / u64::from(self.frame_size)
+    }
+
+    /// The number of whole frames in an area of the given size.
+    pub(crate) fn frame_count(&self, area_size: u64) -> u32 {
+        let count = area_size / u64::from(self.frame_size);
+        u32::try_from(count).unwrap_or(u32::MAX)
+    }
+
+    /// The index of the frame containing an address, if it is within the area.
+    pub(crate) fn frame_index_in(&self, area_size: u64, addr: u64) -> Option<u32> {
+        let idx = self.frame_index(addr);
+        u32::try_from(idx)
+            .ok()
+            .filter(|&idx| idx < self.frame_count(area_size))
+    }
+
+    /// The offsets from the data of an address to the end of its chunk, see [`Umem::chunk_at`].
+    pub(crate) fn chunk_range(&self, area_size: u64, addr: u64) -> Option<Range<u64>> {
+        let frame_size = u64::from(self.frame_size);
+
+        let data = self.resolve_addr(addr);
+        let end = if self.is_unaligned() {
+            let (base, _) = Self::split_unaligned_addr(addr);
+            base.saturating_add(frame_size)
+        } else {
+            (data / frame_size)
+                .saturating_add(1)
+                .saturating_mul(frame_size)
+        };
+
+        let end = end.min(area_size);
+        if data >= end {
+            return None;
+        }
+
+        Some(data..end)
+    }
+
+    /// The offsets of exactly the data of a descriptor, see [`Umem::chunk_of`].
+    pub(crate) fn desc_range(&self, area_size: u64, desc: &XdpDesc) -> Option<Range<u64>> {
+        let range = self.chunk_range(area_size, desc.addr)?;
+        let len = u64::from(desc.len);
+
+        if len > range.end - range.start {
+            return None;
+        }
+
+        Some(range.start..range.start + len)
+    }
+}
+
 impl SocketConfig {
     /// Flag-bit for [`Umem::bind`] that the descriptor is shared.
     ///
@@ -449,6 +1005,51 @@ impl SocketConfig {
     ///
     /// Needs to be set for [`DeviceQueue::needs_wakeup`] and [`RingTx::needs_wakeup`].
     pub const XDP_BIND_NEED_WAKEUP: u16 = 1 << 3;
+    /// Enable support for multi-buffer packets.
+    ///
+    /// Packets larger than a frame are then split over multiple descriptors, chained with
+    /// [`XdpDesc::XDP_PKT_CONTD`]. See [`ReadRx::read_packet`] and [`WriteTx::insert_packet`].
+    ///
+    /// [`ReadRx::read_packet`]: crate::ReadRx::read_packet
+    /// [`WriteTx::insert_packet`]: crate::WriteTx::insert_packet
+    pub const XDP_USE_SG: u16 = 1 << 4;
+}
+
+/// The geometry of the frames in a memory file, recorded in the page after the frames.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct MemfdGeometry {
+    magic: u64,
+    frame_size: u32,
+    frame_count: u32,
+}
+
+impl MemfdGeometry {
+    const MAGIC: u64 = u64::from_le_bytes(*b"xdpilone");
+
+    fn new(config: &UmemConfig, frame_count: u32) -> Self {
+        MemfdGeometry {
+            magic: Self::MAGIC,
+            frame_size: config.frame_size,
+            frame_count,
+        }
+    }
+
+    fn mismatch() -> ConfigError {
+        ConfigError::new(
+            ConfigField::Area,
+            "must match the frame size and count the memory file was created with",
+        )
+    }
+
+    /// The offset of the record and the length of the file, for frames of `len` bytes.
+    fn layout(len: usize) -> Option<(usize, usize)> {
+        let page = PageSize::Base.bytes();
+        let offset = len.checked_add(page - 1)? & !(page - 1);
+        let file_len = offset.checked_add(page)?;
+        isize::try_from(file_len).ok()?;
+        Some((offset, file_len))
+    }
 }
 
 #[derive(Default)]
@@ -469,11 +1070,6 @@ impl super::ControlSet for SpinLockedControlSet {
         lock.insert(ctx)
     }
 
-    fn contains(&self, ctx: &IfCtx) -> bool {
-        let lock = self.inner.read();
-        lock.contains(ctx)
-    }
-
     fn remove(&self, ctx: &IfCtx) {
         let mut lock = self.inner.write();
         lock.remove(ctx);
@@ -490,6 +1086,31 @@ impl UmemChunk {
         self.as_xdp_with_len(len)
     }
 
+    /// Turn into a descriptor for unaligned chunk mode, with an offset into the chunk.
+    ///
+    /// The chunk's own offset is used as the base address while the `offset` is encoded in the
+    /// upper bits of the address, see [`UmemConfig::unaligned_addr`]. Only meaningful if the
+    /// `Umem` has been configured with [`UmemConfig::XDP_UMEM_UNALIGNED_CHUNK_FLAG`].
+    ///
+    /// # Panics
+    ///
+    /// When debug assertions are enabled, this panics if the data range is not within the chunk.
+    pub fn as_xdp_unaligned(self, offset: u16, len: u32) -> XdpDesc {
+        debug_assert!(
+            u64::from(offset) + u64::from(len) <= ptr_len(self.addr.as_ptr()) as u64,
+            "Invalid XDP descriptor range {}+{} for chunk of size {}",
+            offset,
+            len,
+            ptr_len(self.addr.as_ptr()) as u32,
+        );
+
+        XdpDesc {
+            addr: UmemConfig::unaligned_addr(self.offset, offset),
+            len,
+            options: 0,
+        }
+    }
+
     /// Turn into a descriptor with concrete length.
     ///
     /// # Panics
@@ -511,3 +1132,164 @@ impl UmemChunk {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::xdp::XdpDesc;
+    use crate::xsk::UmemConfig;
+
+    const FRAME: u64 = 4096;
+    /// An area of four frames.
+    const AREA: u64 = 4 * FRAME;
+
+    fn unaligned() -> UmemConfig {
+        UmemConfig {
+            flags: UmemConfig::XDP_UMEM_UNALIGNED_CHUNK_FLAG,
+            ..UmemConfig::default()
+        }
+    }
+
+    #[test]
+    fn unaligned_addr_round_trip() {
+        let max_base = UmemConfig::XSK_UNALIGNED_BUF_ADDR_MASK;
+
+        for &base in &[0, 1, 4096, 0x1234_5678_9abc, max_base] {
+            for &offset in &[0, 1, 256, 0x8000, u16::MAX] {
+                let addr = UmemConfig::unaligned_addr(base, offset);
+                assert_eq!(UmemConfig::split_unaligned_addr(addr), (base, offset));
+            }
+        }
+    }
+
+    #[test]
+    fn unaligned_addr_max_offset() {
+        let addr = UmemConfig::unaligned_addr(0, u16::MAX);
+        assert_eq!(addr, 0xffff_0000_0000_0000);
+        assert_eq!(unaligned().resolve_addr(addr), u64::from(u16::MAX));
+
+        let addr = UmemConfig::unaligned_addr(4096, u16::MAX);
+        assert_eq!(unaligned().resolve_addr(addr), 4096 + u64::from(u16::MAX));
+    }
+
+    #[test]
+    fn unaligned_addr_discards_high_base_bits() {
+        let base = 1 << UmemConfig::XSK_UNALIGNED_BUF_OFFSET_SHIFT | 64;
+        let addr = UmemConfig::unaligned_addr(base, 2);
+        assert_eq!(UmemConfig::split_unaligned_addr(addr), (64, 2));
+    }
+
+    #[test]
+    fn resolve_addr_aligned_is_identity() {
+        let config = UmemConfig::default();
+        let addr = UmemConfig::unaligned_addr(4096, 16);
+        assert_eq!(config.resolve_addr(addr), addr);
+        assert_eq!(unaligned().resolve_addr(addr), 4096 + 16);
+    }
+
+    #[test]
+    fn frame_index_aligned() {
+        let config = UmemConfig::default();
+
+        assert_eq!(config.frame_index_in(AREA, 0), Some(0));
+        // Any address within the frame, such as the data after the headroom.
+        assert_eq!(config.frame_index_in(AREA, FRAME + 256), Some(1));
+        assert_eq!(config.frame_index_in(AREA, AREA - 1), Some(3));
+        assert_eq!(config.frame_index_in(AREA, AREA), None);
+        assert_eq!(config.frame_index_in(AREA, u64::MAX), None);
+    }
+
+    #[test]
+    fn frame_index_unaligned() {
+        let config = unaligned();
+
+        // The frame of the base address, not of the data.
+        let addr = UmemConfig::unaligned_addr(FRAME - 64, 128);
+        assert_eq!(config.frame_index_in(AREA, addr), Some(0));
+
+        let addr = UmemConfig::unaligned_addr(3 * FRAME, u16::MAX);
+        assert_eq!(config.frame_index_in(AREA, addr), Some(3));
+
+        let addr = UmemConfig::unaligned_addr(AREA, 0);
+        assert_eq!(config.frame_index_in(AREA, addr), None);
+    }
+
+    #[test]
+    fn frame_count_partial_frame() {
+        let config = unaligned();
+        assert_eq!(config.frame_count(AREA + FRAME / 2), 4);
+        assert_eq!(config.frame_count(FRAME - 1), 0);
+    }
+
+    #[test]
+    fn chunk_range_aligned_headroom() {
+        let config = UmemConfig {
+            headroom: 128,
+            ..UmemConfig::default()
+        };
+
+        // The kernel reports the data after its own and the configured headroom.
+        let data = FRAME + 256 + 128;
+        assert_eq!(config.chunk_range(AREA, data), Some(data..2 * FRAME));
+        assert_eq!(config.chunk_range(AREA, FRAME), Some(FRAME..2 * FRAME));
+        assert_eq!(config.chunk_range(AREA, AREA - 1), Some(AREA - 1..AREA));
+        assert_eq!(config.chunk_range(AREA, AREA), None);
+    }
+
+    #[test]
+    fn chunk_range_unaligned() {
+        let config = unaligned();
+
+        // The chunk extends one frame size past its base, across the frame boundary.
+        let addr = UmemConfig::unaligned_addr(FRAME - 64, 128);
+        assert_eq!(
+            config.chunk_range(AREA, addr),
+            Some(FRAME + 64..2 * FRAME - 64)
+        );
+
+        // Cut off at the end of the area.
+        let addr = UmemConfig::unaligned_addr(AREA - 64, 16);
+        assert_eq!(config.chunk_range(AREA, addr), Some(AREA - 48..AREA));
+
+        // The offset points past the chunk.
+        let addr = UmemConfig::unaligned_addr(0, FRAME as u16);
+        assert_eq!(config.chunk_range(AREA, addr), None);
+
+        let addr = UmemConfig::unaligned_addr(AREA - 64, 64);
+        assert_eq!(config.chunk_range(AREA, addr), None);
+    }
+
+    #[test]
+    fn desc_range_length() {
+        let config = UmemConfig::default();
+        let desc = |addr, len| XdpDesc {
+            addr,
+            len,
+            options: 0,
+        };
+
+        let data = FRAME + 256;
+        assert_eq!(
+            config.desc_range(AREA, &desc(data, 60)),
+            Some(data..data + 60)
+        );
+        assert_eq!(
+            config.desc_range(AREA, &desc(data, (FRAME - 256) as u32)),
+            Some(data..2 * FRAME)
+        );
+        assert_eq!(
+            config.desc_range(AREA, &desc(data, (FRAME - 255) as u32)),
+            None
+        );
+
+        let config = unaligned();
+        let addr = UmemConfig::unaligned_addr(FRAME - 64, 128);
+        assert_eq!(
+            config.desc_range(AREA, &desc(addr, 60)),
+            Some(FRAME + 64..FRAME + 124)
+        );
+        assert_eq!(
+            config.desc_range(AREA, &desc(addr, (FRAME - 128) as u32 + 1)),
+            None
+        );
+    }
+}
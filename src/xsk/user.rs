@@ -1,5 +1,14 @@
-use crate::xdp::XdpDesc;
-use crate::xsk::{BufIdx, DeviceQueue, RingCons, RingProd, RingRx, RingTx};
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+use core::sync::atomic::Ordering;
+use core::time::Duration;
+
+use crate::xdp::{XdpDesc, XskTxMetadata};
+use crate::xsk::{
+    BufIdx, DeviceQueue, RingCons, RingProd, RingRx, RingState, RingTx, SharedTx, SocketFd, Umem,
+    WakeOutcome,
+};
+use crate::Errno;
 
 impl DeviceQueue {
     /// Prepare some buffers for the fill ring.
@@ -7,10 +16,7 @@ impl DeviceQueue {
     /// The argument is an upper bound of buffers. Use the resulting object to pass specific
     /// buffers to the fill queue and commit the write.
     pub fn fill(&mut self, max: u32) -> WriteFill<'_> {
-        WriteFill {
-            idx: BufIdxIter::reserve(&mut self.fcq.prod, max),
-            queue: &mut self.fcq.prod,
-        }
+        WriteFill::new(&mut self.fcq.prod, max)
     }
 
     /// Reap some buffers from the completion ring.
@@ -20,10 +26,27 @@ impl DeviceQueue {
     /// The argument is an upper bound of buffers. Use the resulting object to dequeue specific
     /// buffers from the completion queue and commit the read.
     pub fn complete(&mut self, n: u32) -> ReadComplete<'_> {
-        ReadComplete {
-            idx: BufIdxIter::peek(&mut self.fcq.cons, n),
-            queue: &mut self.fcq.cons,
-        }
+        ReadComplete::new(&mut self.fcq.cons, n)
+    }
+
+    /// Move completed buffers straight back into the fill ring.
+    ///
+    /// Takes up to `max` addresses from the completion ring and inserts them into the fill ring,
+    /// in order, as is common for forwarding where transmitted frames are received into again.
+    /// Only as many addresses are moved as there are both completions and free fill slots, the
+    /// remaining completions stay in the ring. Returns the number of addresses moved.
+    pub fn recycle(&mut self, max: u32) -> u32 {
+        recycle(&mut self.fcq.cons, &mut self.fcq.prod, max)
+    }
+
+    /// Take a snapshot of the state of the fill ring, see [`RingState`].
+    pub fn fill_state(&self) -> RingState {
+        self.fcq.prod.state()
+    }
+
+    /// Take a snapshot of the state of the completion ring, see [`RingState`].
+    pub fn complete_state(&self) -> RingState {
+        self.fcq.cons.state()
     }
 
     /// Return the difference between our the kernel's producer state and our consumer head.
@@ -56,7 +79,7 @@ impl DeviceQueue {
     }
 
     /// Poll the fill queue descriptor, to wake it up.
-    pub fn wake(&mut self) {
+    pub fn wake(&self) {
         // A bit more complex than TX, here we do a full poll on the FD.
         let mut poll = libc::pollfd {
             fd: self.socket.fd.0,
@@ -64,9 +87,29 @@ impl DeviceQueue {
             revents: 0,
         };
 
-        // FIXME: should somehow log this, right?
+        // Errors are not reported by `poll`, see `try_wake`.
         let _err = unsafe { libc::poll(&mut poll as *mut _, 1, 0) };
     }
+
+    /// Wake up the fill queue, and report errors.
+    ///
+    /// If the receive ring of the socket is mapped, this receives (with `MSG_DONTWAIT`) which
+    /// surfaces the state of the socket. Returns [`WakeOutcome::Retry`] for transient conditions,
+    /// such as a driver that is still busy. Other errors are returned as is, notably `ENETDOWN`
+    /// if the interface is down and `ENXIO` if the socket is not bound to a device.
+    ///
+    /// Otherwise, such as for a socket that only shares its fill and completion ring, this polls
+    /// the socket as [`DeviceQueue::wake`] does. Then only errors of the `poll` call itself are
+    /// reported, the kernel does not return the result of the wakeup to it.
+    pub fn try_wake(&self) -> Result<WakeOutcome, Errno> {
+        // Without a receive ring the kernel rejects `recvfrom` with `ENOBUFS`. Once mapped, the
+        // ring stays configured on the socket even when its `RingRx` is dropped.
+        if self.socket.fd.1.rx.load(Ordering::Acquire) {
+            wake_recv(&self.socket.fd)
+        } else {
+            wake_poll(&self.socket.fd)
+        }
+    }
 }
 
 impl Drop for DeviceQueue {
@@ -80,10 +123,7 @@ impl RingRx {
     ///
     /// Returns an iterator over the descriptors.
     pub fn receive(&mut self, n: u32) -> ReadRx<'_> {
-        ReadRx {
-            idx: BufIdxIter::peek(&mut self.ring, n),
-            queue: &mut self.ring,
-        }
+        ReadRx::new(&mut self.ring, n)
     }
 
     /// Query the number of available descriptors.
@@ -96,6 +136,39 @@ impl RingRx {
         self.ring.count_pending()
     }
 
+    /// Take a snapshot of the state of the ring, see [`RingState`].
+    pub fn state(&self) -> RingState {
+        self.ring.state()
+    }
+
+    /// Wait until descriptors are available to receive, or a timeout.
+    ///
+    /// This calls `poll` with `POLLIN` on the socket, which also wakes up the driver to process
+    /// the fill ring if it needs it. Pass `None` to wait indefinitely. Returns `Ok(true)` if
+    /// descriptors are available and `Ok(false)` if the timeout elapsed before. A timeout is
+    /// rounded up to full milliseconds.
+    ///
+    /// Note that the kernel indicates the need for a wakeup of receive processing on the fill
+    /// ring, see [`DeviceQueue::needs_wakeup`].
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<bool, Errno> {
+        poll_fd(&self.fd, libc::POLLIN, timeout)
+    }
+
+    /// Receive (with `MSG_DONTWAIT`) to wake up receive processing.
+    ///
+    /// Call this when [`DeviceQueue::needs_wakeup`] is set for the fill ring of this socket. Any
+    /// error is ignored, see [`RingRx::try_wake`].
+    pub fn wake(&self) {
+        let _ = self.try_wake();
+    }
+
+    /// Receive (with `MSG_DONTWAIT`) to wake up receive processing, and report errors.
+    ///
+    /// See [`DeviceQueue::try_wake`].
+    pub fn try_wake(&self) -> Result<WakeOutcome, Errno> {
+        wake_recv(&self.fd)
+    }
+
     /// Get the raw file descriptor of this RX ring.
     ///
     /// # Safety
@@ -110,16 +183,13 @@ impl RingRx {
 }
 
 impl RingTx {
-    const XDP_RING_NEED_WAKEUP: u32 = 1 << 0;
+    pub(super) const XDP_RING_NEED_WAKEUP: u32 = 1 << 0;
 
     /// Transmit some buffers.
     ///
     /// Returns a proxy that can be fed descriptors.
     pub fn transmit(&mut self, n: u32) -> WriteTx<'_> {
-        WriteTx {
-            idx: BufIdxIter::reserve(&mut self.ring, n),
-            queue: &mut self.ring,
-        }
+        WriteTx::new(&mut self.ring, n)
     }
 
     /// Return the difference between our committed producer state and the kernel's consumer head.
@@ -127,6 +197,11 @@ impl RingTx {
         self.ring.count_pending()
     }
 
+    /// Take a snapshot of the state of the ring, see [`RingState`].
+    pub fn state(&self) -> RingState {
+        self.ring.state()
+    }
+
     /// Query if the transmit queue needs to be woken to proceed receiving.
     ///
     /// This is only accurate if `Umem::XDP_BIND_NEED_WAKEUP` was set.
@@ -135,18 +210,39 @@ impl RingTx {
     }
 
     /// Send a message (with `MSG_DONTWAIT`) to wake up the transmit queue.
+    ///
+    /// Any error is ignored, see [`RingTx::try_wake`].
     pub fn wake(&self) {
-        // FIXME: should somehow log this on failure, right?
-        let _ = unsafe {
-            libc::sendto(
-                self.fd.0,
-                core::ptr::null_mut(),
-                0,
-                libc::MSG_DONTWAIT,
-                core::ptr::null_mut(),
-                0,
-            )
-        };
+        let _ = self.try_wake();
+    }
+
+    /// Send a message (with `MSG_DONTWAIT`) to wake up the transmit queue, and report errors.
+    ///
+    /// Returns [`WakeOutcome::Retry`] for transient conditions, such as a driver that is still
+    /// busy or a full device queue. Other errors are returned as is, notably `ENETDOWN` if the
+    /// interface is down and `ENXIO` if the socket is not bound to a device.
+    pub fn try_wake(&self) -> Result<WakeOutcome, Errno> {
+        wake_send(&self.fd)
+    }
+
+    /// Wait until slots are free to transmit, or a timeout.
+    ///
+    /// This calls `poll` with `POLLOUT` on the socket. Pass `None` to wait indefinitely. Returns
+    /// `Ok(true)` if slots are free and `Ok(false)` if the timeout elapsed before. A timeout is
+    /// rounded up to full milliseconds.
+    ///
+    /// Slots are freed as the kernel consumes descriptors, which may require a [`RingTx::wake`]
+    /// first if [`RingTx::needs_wakeup`] is set.
+    pub fn wait_writable(&self, timeout: Option<Duration>) -> Result<bool, Errno> {
+        poll_fd(&self.fd, libc::POLLOUT, timeout)
+    }
+
+    /// Share the ring between threads for the duration of the borrow.
+    ///
+    /// See [`SharedTx`]. Descriptors inserted through it are published to the kernel right away,
+    /// there is nothing left to commit when it is dropped.
+    pub fn share(&mut self) -> SharedTx<'_> {
+        SharedTx::new(&mut self.ring, Some(&self.fd))
     }
 
     /// Get the raw file descriptor of this TX ring.
@@ -163,6 +259,145 @@ impl RingTx {
     }
 }
 
+impl Drop for RingRx {
+    fn drop(&mut self) {
+        // Allow mapping the ring again, see `User::map_rx`.
+        self.fd.1.rx.store(false, Ordering::Release);
+    }
+}
+
+impl Drop for RingTx {
+    fn drop(&mut self) {
+        // Allow mapping the ring again, see `User::map_tx`.
+        self.fd.1.tx.store(false, Ordering::Release);
+    }
+}
+
+/// Poll a socket for events, returning whether any of them occurred before the timeout.
+fn poll_fd(fd: &SocketFd, events: libc::c_short, timeout: Option<Duration>) -> Result<bool, Errno> {
+    let timeout = match timeout {
+        None => -1,
+        Some(timeout) => {
+            // Round up, a sub-millisecond timeout should not turn into a non-blocking poll.
+            let millis = timeout.as_nanos().saturating_add(999_999) / 1_000_000;
+            libc::c_int::try_from(millis).unwrap_or(libc::c_int::MAX)
+        }
+    };
+
+    let mut poll = libc::pollfd {
+        fd: fd.0,
+        events,
+        revents: 0,
+    };
+
+    let ready = unsafe { libc::poll(&mut poll as *mut _, 1, timeout) };
+
+    if ready < 0 {
+        return Err(Errno::last_os_error());
+    }
+
+    Ok(poll.revents & events != 0)
+}
+
+/// Move up to `max` addresses from a completion ring into a fill ring.
+///
+/// See [`DeviceQueue::recycle`].
+pub(super) fn recycle(comp: &mut RingCons, fill: &mut RingProd, max: u32) -> u32 {
+    let mut read = BufIdx(0);
+    let available = comp.peek(1..=max, &mut read);
+
+    if available == 0 {
+        return 0;
+    }
+
+    // Refresh the cached consumer head if it does not show enough space.
+    fill.count_free(available);
+    let mut write = BufIdx(0);
+    let count = fill.reserve(1..=available, &mut write);
+    comp.cancel(available - count);
+
+    if count == 0 {
+        return 0;
+    }
+
+    // Safety: the completion entries were peeked and the fill slots reserved, both rings have
+    // addresses as their entries. Each slice of completions is copied into at most two slices
+    // of the fill ring.
+    let mut offset = 0;
+    for src in unsafe { comp.slots::<u64>(read, count) } {
+        let src = unsafe { &*(src.as_ptr() as *const [u64]) };
+        let len = src.len() as u32;
+        let dst = unsafe { fill.slots::<u64>(BufIdx(write.0.wrapping_add(offset)), len) };
+        unsafe { copy_to_ring(dst, src) };
+        offset += len;
+    }
+
+    fill.submit(count);
+    comp.release(count);
+    count
+}
+
+/// Send a message (with `MSG_DONTWAIT`) on a socket to wake up its transmit processing.
+pub(super) fn wake_send(fd: &SocketFd) -> Result<WakeOutcome, Errno> {
+    let err = unsafe {
+        libc::sendto(
+            fd.0,
+            core::ptr::null_mut(),
+            0,
+            libc::MSG_DONTWAIT,
+            core::ptr::null_mut(),
+            0,
+        )
+    };
+
+    if err < 0 {
+        return WakeOutcome::from_errno(Errno::last_os_error());
+    }
+
+    Ok(WakeOutcome::Woken)
+}
+
+/// Receive (with `MSG_DONTWAIT`) on a socket to wake up its receive processing.
+fn wake_recv(fd: &SocketFd) -> Result<WakeOutcome, Errno> {
+    let err = unsafe {
+        libc::recvfrom(
+            fd.0,
+            core::ptr::null_mut(),
+            0,
+            libc::MSG_DONTWAIT,
+            core::ptr::null_mut(),
+            core::ptr::null_mut(),
+        )
+    };
+
+    if err < 0 {
+        return WakeOutcome::from_errno(Errno::last_os_error());
+    }
+
+    Ok(WakeOutcome::Woken)
+}
+
+/// Poll a socket without waiting, which wakes up its processing but does not report its errors.
+fn wake_poll(fd: &SocketFd) -> Result<WakeOutcome, Errno> {
+    match poll_fd(fd, 0, Some(Duration::ZERO)) {
+        Ok(_) => Ok(WakeOutcome::Woken),
+        Err(err) => WakeOutcome::from_errno(err),
+    }
+}
+
+impl WakeOutcome {
+    /// Classify an error of a wakeup call as transient, or return it.
+    ///
+    /// Note that `ENOBUFS` is permanent, the kernel reports a socket without the ring to wake.
+    fn from_errno(err: Errno) -> Result<Self, Errno> {
+        match err.get_raw() {
+            libc::EAGAIN | libc::EBUSY | libc::EINTR => Ok(WakeOutcome::Retry(err)),
+            _ => Err(err),
+        }
+    }
+}
+
+#[derive(Clone)]
 struct BufIdxIter {
     /// The base of our operation.
     base: BufIdx,
@@ -225,6 +460,25 @@ pub struct ReadRx<'queue> {
     queue: &'queue mut RingCons,
 }
 
+/// The descriptors of one packet, read from a receive (RX) queue.
+///
+/// Created with [`ReadRx::read_packet`]. Iterates over the descriptors in order, all but the last
+/// of which have the [`XdpDesc::XDP_PKT_CONTD`] option set.
+pub struct RxPacket<'rx> {
+    idx: BufIdxIter,
+    /// The queue we read from.
+    queue: &'rx RingCons,
+}
+
+/// The data of one descriptor, read from a receive (RX) queue.
+///
+/// Created with [`ReadRx::read_frame`]. The view borrows the reader, which ensures that the frame
+/// can not be released to the kernel while its contents are accessible.
+pub struct RxFrame<'rx> {
+    desc: XdpDesc,
+    data: &'rx mut [u8],
+}
+
 impl Iterator for BufIdxIter {
     type Item = BufIdx;
     fn next(&mut self) -> Option<BufIdx> {
@@ -259,6 +513,15 @@ impl BufIdxIter {
         this
     }
 
+    /// Take up to `n` of the remaining buffers at once, returning the first and the count.
+    fn advance(&mut self, n: usize) -> (BufIdx, u32) {
+        let count = u32::try_from(n).unwrap_or(u32::MAX).min(self.remain);
+        let base = self.base;
+        self.base.0 = self.base.0.wrapping_add(count);
+        self.remain -= count;
+        (base, count)
+    }
+
     fn commit_prod(&mut self, queue: &mut RingProd) {
         // This contains an atomic write, which LLVM won't even try to optimize away.
         // But, as long as queues are filled there's a decent chance that we didn't manage to
@@ -269,8 +532,8 @@ impl BufIdxIter {
         if self.buffers > 0 {
             let count = self.buffers - self.remain;
             queue.submit(count);
+            // The base was already advanced over these buffers when they were written.
             self.buffers -= count;
-            self.base.0 += count;
         }
     }
 
@@ -280,7 +543,16 @@ impl BufIdxIter {
             let count = self.buffers - self.remain;
             queue.release(count);
             self.buffers -= count;
-            self.base.0 += count;
+        }
+    }
+}
+
+impl<'queue> WriteFill<'queue> {
+    /// Reserve up to `n` slots in a fill ring.
+    pub(crate) fn new(queue: &'queue mut RingProd, n: u32) -> Self {
+        WriteFill {
+            idx: BufIdxIter::reserve(queue, n),
+            queue,
         }
     }
 }
@@ -291,6 +563,11 @@ impl WriteFill<'_> {
         self.idx.buffers
     }
 
+    /// The number of slots that have not been written yet.
+    pub(crate) fn remaining(&self) -> u32 {
+        self.idx.remain
+    }
+
     /// Fill one device descriptor to be filled.
     ///
     /// A descriptor is an offset in the respective Umem's memory. Any offset within a chunk can
@@ -309,6 +586,8 @@ impl WriteFill<'_> {
     /// total number of slots filled.
     pub fn insert(&mut self, it: impl Iterator<Item = u64>) -> u32 {
         let mut n = 0;
+        // Limit the iterator first, it must not be polled for an item that has no slot.
+        let it = it.take(self.idx.remain as usize);
         for (item, bufidx) in it.zip(self.idx.by_ref()) {
             n += 1;
             unsafe { *self.queue.fill_addr(bufidx).as_ptr() = item };
@@ -316,6 +595,41 @@ impl WriteFill<'_> {
         n
     }
 
+    /// Fill additional slots with addresses copied from a slice.
+    ///
+    /// Inserts as many addresses from the start of the slice as slots remain, with at most two
+    /// bulk copies. Returns the number of slots filled.
+    pub fn insert_slice(&mut self, addrs: &[u64]) -> u32 {
+        let (base, count) = self.idx.advance(addrs.len());
+        // Safety: the slots were reserved in the fill ring, of which entries are addresses.
+        let slots = unsafe { self.queue.slots::<u64>(base, count) };
+        unsafe { copy_to_ring(slots, &addrs[..count as usize]) };
+        count
+    }
+
+    /// Get the remaining slots, as up to two slices into the ring.
+    ///
+    /// Like [`VecDeque::as_mut_slices`](alloc::collections::VecDeque::as_mut_slices), the second
+    /// slice is only non-empty if the slots wrap around the end of the ring. Write addresses into
+    /// a prefix of the slots, in order, then call [`WriteFill::mark_written`].
+    pub fn as_mut_slices(&mut self) -> (&mut [MaybeUninit<u64>], &mut [MaybeUninit<u64>]) {
+        // Safety: the slots were reserved in the fill ring, of which entries are addresses.
+        let slots = unsafe { self.queue.slots(self.idx.base, self.idx.remain) };
+        // Safety: reserved slots are not accessed by the kernel, we borrow the writer uniquely.
+        unsafe { as_mut_slices(slots) }
+    }
+
+    /// Mark slots written through [`WriteFill::as_mut_slices`], to be committed.
+    ///
+    /// # Safety
+    ///
+    /// The first `n` remaining slots must have been initialized with addresses of frames that the
+    /// kernel may overwrite. At most the remaining number of slots can be marked.
+    pub unsafe fn mark_written(&mut self, n: u32) {
+        assert!(n <= self.idx.remain, "Marked more slots than remain");
+        self.idx.advance(n as usize);
+    }
+
     /// Commit the previously written buffers to the kernel.
     pub fn commit(&mut self) {
         self.idx.commit_prod(self.queue)
@@ -331,6 +645,16 @@ impl Drop for WriteFill<'_> {
     }
 }
 
+impl<'queue> ReadComplete<'queue> {
+    /// Peek up to `n` entries of a completion ring.
+    pub(crate) fn new(queue: &'queue mut RingCons, n: u32) -> Self {
+        ReadComplete {
+            idx: BufIdxIter::peek(queue, n),
+            queue,
+        }
+    }
+}
+
 impl ReadComplete<'_> {
     /// The total number of available buffers.
     pub fn capacity(&self) -> u32 {
@@ -344,6 +668,37 @@ impl ReadComplete<'_> {
         Some(unsafe { *self.queue.comp_addr(bufidx).as_ptr() })
     }
 
+    /// Read addresses into a slice.
+    ///
+    /// Reads as many addresses as fit into the slice or are available, with at most two bulk
+    /// copies. Returns the number of addresses read.
+    pub fn read_into(&mut self, addrs: &mut [u64]) -> u32 {
+        let (base, count) = self.idx.advance(addrs.len());
+        // Safety: the entries were peeked in the completion ring, of which entries are addresses.
+        let slots = unsafe { self.queue.slots::<u64>(base, count) };
+        unsafe { copy_from_ring(slots, &mut addrs[..count as usize]) };
+        count
+    }
+
+    /// Get the remaining addresses, as up to two slices into the ring.
+    ///
+    /// Like [`VecDeque::as_slices`](alloc::collections::VecDeque::as_slices), the second slice is
+    /// only non-empty if the entries wrap around the end of the ring. Process the addresses in
+    /// place, then call [`ReadComplete::mark_read`] to have them released.
+    pub fn as_slices(&self) -> (&[u64], &[u64]) {
+        // Safety: the entries were peeked in the completion ring, of which entries are addresses.
+        let [head, tail] = unsafe { self.queue.slots(self.idx.base, self.idx.remain) };
+        // Safety: the kernel does not modify peeked entries until they are released.
+        unsafe { (head.as_ref(), tail.as_ref()) }
+    }
+
+    /// Mark addresses as read, as if by [`ReadComplete::read`].
+    ///
+    /// At most the remaining number of addresses are marked. Returns the number marked.
+    pub fn mark_read(&mut self, n: u32) -> u32 {
+        self.idx.advance(n as usize).1
+    }
+
     /// Commit some of the written buffers to the kernel.
     pub fn release(&mut self) {
         self.idx.release_cons(self.queue)
@@ -367,6 +722,16 @@ impl Iterator for ReadComplete<'_> {
     }
 }
 
+impl<'queue> WriteTx<'queue> {
+    /// Reserve up to `n` slots in a transmit ring.
+    pub(crate) fn new(queue: &'queue mut RingProd, n: u32) -> Self {
+        WriteTx {
+            idx: BufIdxIter::reserve(queue, n),
+            queue,
+        }
+    }
+}
+
 impl WriteTx<'_> {
     /// The total number of available slots.
     pub fn capacity(&self) -> u32 {
@@ -381,6 +746,92 @@ impl WriteTx<'_> {
         self.insert(core::iter::once(nr)) > 0
     }
 
+    /// Insert a descriptor to be sent, with a metadata request in front of its data.
+    ///
+    /// The metadata is written into the `tx_metadata_len` bytes immediately preceding the data of
+    /// the descriptor and the [`XdpDesc::XDP_TX_METADATA`] option is set. Returns false if the
+    /// ring is full, or if the metadata area of the descriptor is not valid. That is, if the
+    /// `Umem` was configured with a `tx_metadata_len` smaller than [`XskTxMetadata`], or the
+    /// metadata area is not within the `Umem` (and within the same frame in aligned mode).
+    ///
+    /// # Safety
+    ///
+    /// The `umem` must be the one this ring was bound to. The caller must ensure that the memory
+    /// in front of the frame is not accessed concurrently, in particular by the kernel. That is,
+    /// the frame must not be in any of the rings.
+    pub unsafe fn insert_with_metadata(
+        &mut self,
+        umem: &Umem,
+        mut desc: XdpDesc,
+        meta: &XskTxMetadata,
+    ) -> bool {
+        if self.idx.remain == 0 {
+            return false;
+        }
+
+        let meta_len = umem.config.tx_metadata_len;
+        if (meta_len as usize) < core::mem::size_of::<XskTxMetadata>() {
+            return false;
+        }
+
+        let data = umem.resolve_addr(desc.addr);
+        let area = match data
+            .checked_sub(u64::from(meta_len))
+            .and_then(|start| umem.chunk_at_offset(start, meta_len))
+        {
+            Some(area) => area,
+            None => return false,
+        };
+
+        // Safety: the chunk is in-bounds of the Umem, and the caller guarantees exclusive access.
+        // The data may not be aligned for the struct in unaligned chunk mode.
+        unsafe {
+            area.addr
+                .cast::<XskTxMetadata>()
+                .as_ptr()
+                .write_unaligned(*meta)
+        };
+
+        desc.options |= XdpDesc::XDP_TX_METADATA;
+        self.insert_once(desc)
+    }
+
+    /// Insert all descriptors of a multi-buffer packet, or none of them.
+    ///
+    /// The [`XdpDesc::XDP_PKT_CONTD`] option is set on all but the last descriptor and cleared on
+    /// the last, chaining them into one packet. Returns false, without inserting anything, if
+    /// fewer slots remain than descriptors are passed, or if no descriptors are passed at all.
+    ///
+    /// The socket must be bound with [`SocketConfig::XDP_USE_SG`] for packets of more than one
+    /// descriptor. Note that the kernel further limits the number of descriptors per packet, such
+    /// chains are otherwise dropped as invalid.
+    ///
+    /// [`SocketConfig::XDP_USE_SG`]: crate::SocketConfig::XDP_USE_SG
+    pub fn insert_packet(&mut self, frags: &[XdpDesc]) -> bool {
+        let (last, init) = match frags.split_last() {
+            Some(split) => split,
+            None => return false,
+        };
+
+        if u32::try_from(frags.len()).map_or(true, |len| len > self.idx.remain) {
+            return false;
+        }
+
+        let contd = init.iter().map(|desc| XdpDesc {
+            options: desc.options | XdpDesc::XDP_PKT_CONTD,
+            ..*desc
+        });
+
+        let last = XdpDesc {
+            options: last.options & !XdpDesc::XDP_PKT_CONTD,
+            ..*last
+        };
+
+        let n = self.insert(contd.chain(core::iter::once(last)));
+        debug_assert_eq!(n as usize, frags.len());
+        true
+    }
+
     /// Fill the transmit ring from an iterator.
     ///
     /// Returns the total number of enqueued descriptor. This is a `u32` as it is the common
@@ -388,7 +839,8 @@ impl WriteTx<'_> {
     /// iterator for a more intrusive callback.
     pub fn insert(&mut self, it: impl Iterator<Item = XdpDesc>) -> u32 {
         let mut n = 0;
-        // FIXME: incorrect iteration order? Some items may get consumed but not inserted.
+        // Limit the iterator first, it must not be polled for an item that has no slot.
+        let it = it.take(self.idx.remain as usize);
         for (item, bufidx) in it.zip(self.idx.by_ref()) {
             n += 1;
             unsafe { *self.queue.tx_desc(bufidx).as_ptr() = item };
@@ -396,6 +848,41 @@ impl WriteTx<'_> {
         n
     }
 
+    /// Insert descriptors copied from a slice.
+    ///
+    /// Inserts as many descriptors from the start of the slice as slots remain, with at most two
+    /// bulk copies. Returns the number of descriptors inserted.
+    pub fn insert_slice(&mut self, descs: &[XdpDesc]) -> u32 {
+        let (base, count) = self.idx.advance(descs.len());
+        // Safety: the slots were reserved in the transmit ring, of which entries are descriptors.
+        let slots = unsafe { self.queue.slots::<XdpDesc>(base, count) };
+        unsafe { copy_to_ring(slots, &descs[..count as usize]) };
+        count
+    }
+
+    /// Get the remaining slots, as up to two slices into the ring.
+    ///
+    /// Like [`VecDeque::as_mut_slices`](alloc::collections::VecDeque::as_mut_slices), the second
+    /// slice is only non-empty if the slots wrap around the end of the ring. Write descriptors
+    /// into a prefix of the slots, in order, then call [`WriteTx::mark_written`].
+    pub fn as_mut_slices(&mut self) -> (&mut [MaybeUninit<XdpDesc>], &mut [MaybeUninit<XdpDesc>]) {
+        // Safety: the slots were reserved in the transmit ring, of which entries are descriptors.
+        let slots = unsafe { self.queue.slots(self.idx.base, self.idx.remain) };
+        // Safety: reserved slots are not accessed by the kernel, we borrow the writer uniquely.
+        unsafe { as_mut_slices(slots) }
+    }
+
+    /// Mark slots written through [`WriteTx::as_mut_slices`], to be committed.
+    ///
+    /// # Safety
+    ///
+    /// The first `n` remaining slots must have been initialized with descriptors of frames that
+    /// the kernel may read. At most the remaining number of slots can be marked.
+    pub unsafe fn mark_written(&mut self, n: u32) {
+        assert!(n <= self.idx.remain, "Marked more slots than remain");
+        self.idx.advance(n as usize);
+    }
+
     /// Commit the previously written buffers to the kernel.
     pub fn commit(&mut self) {
         self.idx.commit_prod(self.queue);
@@ -411,6 +898,16 @@ impl Drop for WriteTx<'_> {
     }
 }
 
+impl<'queue> ReadRx<'queue> {
+    /// Peek up to `n` entries of a receive ring.
+    pub(crate) fn new(queue: &'queue mut RingCons, n: u32) -> Self {
+        ReadRx {
+            idx: BufIdxIter::peek(queue, n),
+            queue,
+        }
+    }
+}
+
 impl ReadRx<'_> {
     /// The total number of available buffers.
     pub fn capacity(&self) -> u32 {
@@ -426,6 +923,116 @@ impl ReadRx<'_> {
         Some(unsafe { *self.queue.rx_desc(bufidx).as_ptr() })
     }
 
+    /// Read one descriptor from the receive ring, with access to the packet data.
+    ///
+    /// The view contains exactly the `len` bytes of the descriptor. It borrows this reader, so it
+    /// must be dropped before reading the next descriptor and, in particular, before the frame is
+    /// handed back to the kernel with [`ReadRx::release`].
+    ///
+    /// Note that the kernel does not write to a frame after it has been returned in the receive
+    /// ring. It is the other uses of the frame that the library can not track.
+    ///
+    /// # Safety
+    ///
+    /// The `umem` must be the `Umem` the socket was bound to. The frame of the descriptor must not
+    /// be accessed in any other way while the view is alive. In particular, it must not be in the
+    /// fill or transmit ring at the same time, for instance because the descriptor was duplicated,
+    /// and no other reference to its memory, such as through a [`crate::UmemChunk`], may be used.
+    /// A [`crate::FramePool`] helps to uphold this by tracking the owner of each frame.
+    ///
+    /// # Panics
+    ///
+    /// If the descriptor does not refer to memory within `umem`. Descriptors returned by the
+    /// kernel always do, as long as `umem` is the `Umem` the socket was bound to.
+    pub unsafe fn read_frame<'rx>(&'rx mut self, umem: &'rx Umem) -> Option<RxFrame<'rx>> {
+        let desc = self.read()?;
+        let chunk = umem
+            .chunk_of(&desc)
+            .expect("Descriptor not within the Umem area, the socket is bound to another Umem");
+
+        // Safety: the chunk is within the Umem area. Until released, the kernel no longer writes
+        // to it and the caller guarantees there is no other access to the frame. We borrow the
+        // reader exclusively, so the view ends before the frame is released.
+        let data = unsafe { &mut *chunk.addr.as_ptr() };
+        Some(RxFrame { desc, data })
+    }
+
+    /// Read all descriptors of the next, possibly multi-buffer, packet.
+    ///
+    /// When the socket is bound with [`SocketConfig::XDP_USE_SG`], the kernel splits packets
+    /// larger than a frame over consecutive descriptors. All but the last of those have the
+    /// [`XdpDesc::XDP_PKT_CONTD`] option set. This groups such a chain into one packet.
+    ///
+    /// Returns `None` if no complete packet is available, in which case no descriptor is read.
+    /// The kernel publishes all descriptors of a packet together, but a packet may be cut off at
+    /// the end of the descriptors available to this reader. Receive a larger batch to read it.
+    ///
+    /// [`SocketConfig::XDP_USE_SG`]: crate::SocketConfig::XDP_USE_SG
+    pub fn read_packet(&mut self) -> Option<RxPacket<'_>> {
+        let mut count = 0;
+
+        loop {
+            if count >= self.idx.remain {
+                return None;
+            }
+
+            let bufidx = BufIdx(self.idx.base.0.wrapping_add(count));
+            // Safety: the buffer is from that same queue by construction, and within the
+            // remaining buffers that were peeked.
+            let desc = unsafe { *self.queue.rx_desc(bufidx).as_ptr() };
+            count += 1;
+
+            if desc.options & XdpDesc::XDP_PKT_CONTD == 0 {
+                break;
+            }
+        }
+
+        let idx = BufIdxIter {
+            base: self.idx.base,
+            buffers: count,
+            remain: count,
+        };
+
+        self.idx.base.0 = self.idx.base.0.wrapping_add(count);
+        self.idx.remain -= count;
+
+        Some(RxPacket {
+            idx,
+            queue: self.queue,
+        })
+    }
+
+    /// Read descriptors into a slice.
+    ///
+    /// Reads as many descriptors as fit into the slice or are available, with at most two bulk
+    /// copies. Returns the number of descriptors read.
+    pub fn read_into(&mut self, descs: &mut [XdpDesc]) -> u32 {
+        let (base, count) = self.idx.advance(descs.len());
+        // Safety: the entries were peeked in the receive ring, of which entries are descriptors.
+        let slots = unsafe { self.queue.slots::<XdpDesc>(base, count) };
+        unsafe { copy_from_ring(slots, &mut descs[..count as usize]) };
+        count
+    }
+
+    /// Get the remaining descriptors, as up to two slices into the ring.
+    ///
+    /// Like [`VecDeque::as_slices`](alloc::collections::VecDeque::as_slices), the second slice is
+    /// only non-empty if the entries wrap around the end of the ring. Process the descriptors in
+    /// place, then call [`ReadRx::mark_read`] to have them released.
+    pub fn as_slices(&self) -> (&[XdpDesc], &[XdpDesc]) {
+        // Safety: the entries were peeked in the receive ring, of which entries are descriptors.
+        let [head, tail] = unsafe { self.queue.slots(self.idx.base, self.idx.remain) };
+        // Safety: the kernel does not modify peeked entries until they are released.
+        unsafe { (head.as_ref(), tail.as_ref()) }
+    }
+
+    /// Mark descriptors as read, as if by [`ReadRx::read`].
+    ///
+    /// At most the remaining number of descriptors are marked. Returns the number marked.
+    pub fn mark_read(&mut self, n: u32) -> u32 {
+        self.idx.advance(n as usize).1
+    }
+
     /// Commit some of the written buffers to the kernel.
     pub fn release(&mut self) {
         self.idx.release_cons(self.queue)
@@ -448,3 +1055,94 @@ impl Iterator for ReadRx<'_> {
         self.read()
     }
 }
+
+impl RxPacket<'_> {
+    /// The total number of bytes, over all remaining descriptors of the packet.
+    pub fn total_len(&self) -> u64 {
+        let iter = RxPacket {
+            idx: self.idx.clone(),
+            queue: self.queue,
+        };
+
+        iter.map(|desc| u64::from(desc.len)).sum()
+    }
+}
+
+impl Iterator for RxPacket<'_> {
+    type Item = XdpDesc;
+
+    fn next(&mut self) -> Option<XdpDesc> {
+        let bufidx = self.idx.next()?;
+        // Safety: the buffer is from that same queue by construction.
+        Some(unsafe { *self.queue.rx_desc(bufidx).as_ptr() })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remain = self.idx.remain as usize;
+        (remain, Some(remain))
+    }
+}
+
+impl ExactSizeIterator for RxPacket<'_> {}
+
+/// Convert the two slices of a ring, as returned by `slots`, to uninitialized slots.
+///
+/// # Safety
+///
+/// The slots must be valid for writes and not aliased for the chosen lifetime.
+unsafe fn as_mut_slices<'a, T>(
+    [head, tail]: [NonNull<[T]>; 2],
+) -> (&'a mut [MaybeUninit<T>], &'a mut [MaybeUninit<T>]) {
+    let cast = |slots: NonNull<[T]>| {
+        let slots = slots.as_ptr() as *mut [MaybeUninit<T>];
+        unsafe { &mut *slots }
+    };
+    (cast(head), cast(tail))
+}
+
+/// Copy entries into the two slices of a ring, as returned by `slots`.
+///
+/// # Safety
+///
+/// The slots must be valid for writes and together exactly as long as `src`.
+pub(super) unsafe fn copy_to_ring<T: Copy>([head, tail]: [NonNull<[T]>; 2], src: &[T]) {
+    let (first, second) = src.split_at(head.len());
+    debug_assert_eq!(second.len(), tail.len());
+    let copy = |src: &[T], dst: NonNull<[T]>| unsafe {
+        core::ptr::copy_nonoverlapping(src.as_ptr(), dst.as_ptr() as *mut T, src.len())
+    };
+    copy(first, head);
+    copy(second, tail);
+}
+
+/// Copy entries from the two slices of a ring, as returned by `slots`.
+///
+/// # Safety
+///
+/// The slots must be valid for reads and together exactly as long as `dst`.
+unsafe fn copy_from_ring<T: Copy>([head, tail]: [NonNull<[T]>; 2], dst: &mut [T]) {
+    let (first, second) = dst.split_at_mut(head.len());
+    debug_assert_eq!(second.len(), tail.len());
+    let copy = |src: NonNull<[T]>, dst: &mut [T]| unsafe {
+        core::ptr::copy_nonoverlapping(src.as_ptr() as *const T, dst.as_mut_ptr(), dst.len())
+    };
+    copy(head, first);
+    copy(tail, second);
+}
+
+impl RxFrame<'_> {
+    /// The descriptor of this frame, as returned by the kernel.
+    pub fn desc(&self) -> XdpDesc {
+        self.desc
+    }
+
+    /// The received bytes.
+    pub fn data(&self) -> &[u8] {
+        self.data
+    }
+
+    /// The received bytes, for modification in-place.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        self.data
+    }
+}
@@ -0,0 +1,455 @@
+use crate::mmap::PageSize;
+use crate::xsk::{ConfigError, ConfigField, SetupError, SocketConfig, UmemConfig};
+use crate::Errno;
+
+impl UmemConfig {
+    /// The smallest frame size accepted by the kernel, `XDP_UMEM_MIN_CHUNK_SIZE`.
+    pub const MIN_FRAME_SIZE: u32 = 2048;
+    /// The largest frame size accepted by any kernel, `XDP_UMEM_MAX_CHUNK_SIZE` of Linux 6.6.
+    ///
+    /// Frames larger than the base page size are only accepted since that version, and only if
+    /// the area is backed by huge pages. Older kernels limit frames to the page size.
+    pub const MAX_FRAME_SIZE: u32 = 1 << 16;
+    /// The headroom the kernel reserves in each frame for itself, `XDP_PACKET_HEADROOM`.
+    const XDP_PACKET_HEADROOM: u32 = 256;
+    /// All flags known to the kernel, `XDP_UMEM_FLAGS_VALID`.
+    const XDP_UMEM_FLAGS_VALID: u32 = Self::XDP_UMEM_UNALIGNED_CHUNK_FLAG
+        | Self::XDP_UMEM_TX_SW_CSUM
+        | Self::XDP_UMEM_TX_METADATA_LEN;
+
+    /// Check the configuration against the constraints of the kernel.
+    ///
+    /// This does not make any system call. The checks are the ones all kernels with AF_XDP support
+    /// agree on, a kernel may still reject a configuration that passes. Notably, newer kernels
+    /// reserve some additional tailroom in each frame and permit less headroom than checked here.
+    /// Whether a frame larger than the page size is accepted depends on the kernel version and the
+    /// pages backing the area, which is left to the kernel, see [`UmemConfig::MAX_FRAME_SIZE`].
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        Self::check_ring(ConfigField::FillSize, self.fill_size)?;
+        Self::check_ring(ConfigField::CompleteSize, self.complete_size)?;
+
+        if self.flags & !Self::XDP_UMEM_FLAGS_VALID != 0 {
+            return Err(ConfigError::new(
+                ConfigField::Flags,
+                "contains unknown flags",
+            ));
+        }
+
+        if self.frame_size < Self::MIN_FRAME_SIZE {
+            return Err(ConfigError::new(
+                ConfigField::FrameSize,
+                "must be at least 2048 bytes",
+            ));
+        }
+
+        if self.frame_size > Self::MAX_FRAME_SIZE {
+            return Err(ConfigError::new(
+                ConfigField::FrameSize,
+                "must not be larger than 64 KiB",
+            ));
+        }
+
+        if !self.is_unaligned() && !self.frame_size.is_power_of_two() {
+            return Err(ConfigError::new(
+                ConfigField::FrameSize,
+                "must be a power of two unless the unaligned chunk mode is used",
+            ));
+        }
+
+        if self.headroom >= self.frame_size - Self::XDP_PACKET_HEADROOM {
+            return Err(ConfigError::new(
+                ConfigField::Headroom,
+                "must leave room for the kernel's headroom of 256 bytes in the frame",
+            ));
+        }
+
+        if self.tx_metadata_len >= 256 || self.tx_metadata_len % 8 != 0 {
+            return Err(ConfigError::new(
+                ConfigField::TxMetadataLen,
+                "must be a multiple of 8 and less than 256",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Check the configuration and the length of a memory area for a `Umem`.
+    ///
+    /// See [`UmemConfig::validate`]. The area must hold at least one frame, and exactly a whole
+    /// number of them unless the unaligned chunk mode is used.
+    pub fn validate_area(&self, len: usize) -> Result<(), ConfigError> {
+        self.validate()?;
+
+        let len = len as u64;
+        let frame_size = u64::from(self.frame_size);
+        let page_size = PageSize::Base.bytes() as u64;
+        let pages = len / page_size + u64::from(len % page_size != 0);
+
+        if len < frame_size {
+            return Err(ConfigError::new(
+                ConfigField::Area,
+                "must hold at least one frame",
+            ));
+        }
+
+        if !self.is_unaligned() && len % frame_size != 0 {
+            return Err(ConfigError::new(
+                ConfigField::Area,
+                "must be a multiple of the frame size unless the unaligned chunk mode is used",
+            ));
+        }
+
+        if pages > u64::from(u32::MAX) {
+            return Err(ConfigError::new(
+                ConfigField::Area,
+                "must not consist of more than 2^32 pages",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn check_ring(field: ConfigField, size: u32) -> Result<(), ConfigError> {
+        if !size.is_power_of_two() {
+            return Err(ConfigError::new(field, "must be a non-zero power of two"));
+        }
+
+        Ok(())
+    }
+}
+
+impl SocketConfig {
+    /// Check the configuration against the constraints of the kernel.
+    ///
+    /// This does not make any system call. See [`UmemConfig::validate`].
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        const XDP_BIND_VALID: u16 = SocketConfig::XDP_BIND_SHARED_UMEM
+            | SocketConfig::XDP_BIND_COPY
+            | SocketConfig::XDP_BIND_ZEROCOPY
+            | SocketConfig::XDP_BIND_NEED_WAKEUP
+            | SocketConfig::XDP_USE_SG;
+
+        if let Some(size) = self.rx_size {
+            UmemConfig::check_ring(ConfigField::RxSize, size.get())?;
+        }
+
+        if let Some(size) = self.tx_size {
+            UmemConfig::check_ring(ConfigField::TxSize, size.get())?;
+        }
+
+        if self.rx_size.is_none() && self.tx_size.is_none() {
+            return Err(ConfigError::new(
+                ConfigField::RxSize,
+                "a socket needs a receive or transmit ring, or both",
+            ));
+        }
+
+        if self.bind_flags & !XDP_BIND_VALID != 0 {
+            return Err(ConfigError::new(
+                ConfigField::BindFlags,
+                "contains unknown flags",
+            ));
+        }
+
+        let exclusive = Self::XDP_BIND_COPY | Self::XDP_BIND_ZEROCOPY;
+        if self.bind_flags & exclusive == exclusive {
+            return Err(ConfigError::new(
+                ConfigField::BindFlags,
+                "can not force both copy and zero-copy mode",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl ConfigError {
+    pub(crate) fn new(field: ConfigField, reason: &'static str) -> Self {
+        ConfigError { field, reason }
+    }
+
+    /// The field with the invalid value.
+    pub fn field(&self) -> ConfigField {
+        self.field
+    }
+
+    /// A description of the constraint that the value violates.
+    pub fn reason(&self) -> &'static str {
+        self.reason
+    }
+}
+
+impl ConfigField {
+    /// The name of the field, as in the struct definition.
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfigField::FillSize => "fill_size",
+            ConfigField::CompleteSize => "complete_size",
+            ConfigField::FrameSize => "frame_size",
+            ConfigField::Headroom => "headroom",
+            ConfigField::Flags => "flags",
+            ConfigField::TxMetadataLen => "tx_metadata_len",
+            ConfigField::Area => "area",
+            ConfigField::RxSize => "rx_size",
+            ConfigField::TxSize => "tx_size",
+            ConfigField::BindFlags => "bind_flags",
+        }
+    }
+}
+
+impl core::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "invalid `{}`: {}", self.field.name(), self.reason)
+    }
+}
+
+impl SetupError {
+    /// The invalid configuration, if this is not an error of the system.
+    pub fn config(&self) -> Option<&ConfigError> {
+        match self {
+            SetupError::Config(err) => Some(err),
+            SetupError::Os(_) => None,
+        }
+    }
+}
+
+impl core::fmt::Display for SetupError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SetupError::Config(err) => err.fmt(f),
+            SetupError::Os(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<ConfigError> for SetupError {
+    fn from(err: ConfigError) -> Self {
+        SetupError::Config(err)
+    }
+}
+
+impl From<Errno> for SetupError {
+    fn from(err: Errno) -> Self {
+        SetupError::Os(err)
+    }
+}
+
+/// For callers that only handle an `Errno`, an invalid configuration is `EINVAL`.
+impl From<SetupError> for Errno {
+    fn from(err: SetupError) -> Self {
+        match err {
+            SetupError::Config(_) => Errno(libc::EINVAL),
+            SetupError::Os(err) => err,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU32;
+
+    use crate::xsk::{ConfigError, ConfigField, SocketConfig, UmemConfig};
+
+    fn umem_field(config: UmemConfig) -> ConfigField {
+        config.validate().unwrap_err().field()
+    }
+
+    fn area_field(config: &UmemConfig, len: usize) -> ConfigField {
+        config.validate_area(len).unwrap_err().field()
+    }
+
+    fn socket_field(config: SocketConfig) -> ConfigField {
+        config.validate().unwrap_err().field()
+    }
+
+    fn socket() -> SocketConfig {
+        SocketConfig {
+            rx_size: NonZeroU32::new(8),
+            tx_size: NonZeroU32::new(8),
+            bind_flags: 0,
+        }
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        UmemConfig::default().validate().unwrap();
+        socket().validate().unwrap();
+    }
+
+    #[test]
+    fn ring_sizes() {
+        for size in [0, 3, 1000] {
+            let config = UmemConfig {
+                fill_size: size,
+                ..UmemConfig::default()
+            };
+            assert_eq!(umem_field(config), ConfigField::FillSize);
+
+            let config = UmemConfig {
+                complete_size: size,
+                ..UmemConfig::default()
+            };
+            assert_eq!(umem_field(config), ConfigField::CompleteSize);
+
+            let config = SocketConfig {
+                rx_size: NonZeroU32::new(size),
+                ..socket()
+            };
+            let expected = if size == 0 {
+                // Not a ring at all, which is fine as long as there is a transmit ring.
+                None
+            } else {
+                Some(ConfigField::RxSize)
+            };
+            assert_eq!(config.validate().err().map(|e| e.field()), expected);
+
+            let config = SocketConfig {
+                tx_size: NonZeroU32::new(size),
+                ..socket()
+            };
+            let expected = if size == 0 {
+                None
+            } else {
+                Some(ConfigField::TxSize)
+            };
+            assert_eq!(config.validate().err().map(|e| e.field()), expected);
+        }
+    }
+
+    #[test]
+    fn unknown_flags() {
+        let config = UmemConfig {
+            flags: 1 << 7,
+            ..UmemConfig::default()
+        };
+        assert_eq!(umem_field(config), ConfigField::Flags);
+    }
+
+    #[test]
+    fn frame_size_bounds() {
+        for frame_size in [0, 1024, UmemConfig::MIN_FRAME_SIZE - 1] {
+            let config = UmemConfig {
+                frame_size,
+                ..UmemConfig::default()
+            };
+            assert_eq!(umem_field(config), ConfigField::FrameSize);
+        }
+
+        let config = UmemConfig {
+            frame_size: UmemConfig::MAX_FRAME_SIZE * 2,
+            ..UmemConfig::default()
+        };
+        assert_eq!(umem_field(config), ConfigField::FrameSize);
+
+        // Larger than a base page, left to the kernel.
+        let config = UmemConfig {
+            frame_size: UmemConfig::MAX_FRAME_SIZE,
+            ..UmemConfig::default()
+        };
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn frame_size_power_of_two() {
+        let config = UmemConfig {
+            frame_size: 3000,
+            ..UmemConfig::default()
+        };
+        assert_eq!(umem_field(config.clone()), ConfigField::FrameSize);
+
+        let config = UmemConfig {
+            flags: UmemConfig::XDP_UMEM_UNALIGNED_CHUNK_FLAG,
+            ..config
+        };
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn headroom() {
+        let config = UmemConfig {
+            headroom: 4096 - 256,
+            ..UmemConfig::default()
+        };
+        assert_eq!(umem_field(config), ConfigField::Headroom);
+
+        let config = UmemConfig {
+            headroom: 4096 - 257,
+            ..UmemConfig::default()
+        };
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn tx_metadata_len() {
+        for tx_metadata_len in [4, 256, 260] {
+            let config = UmemConfig {
+                tx_metadata_len,
+                ..UmemConfig::default()
+            };
+            assert_eq!(umem_field(config), ConfigField::TxMetadataLen);
+        }
+    }
+
+    #[test]
+    fn area() {
+        let config = UmemConfig::default();
+        config.validate_area(4096).unwrap();
+        assert_eq!(area_field(&config, 0), ConfigField::Area);
+        assert_eq!(area_field(&config, 4095), ConfigField::Area);
+        assert_eq!(area_field(&config, 4096 + 2048), ConfigField::Area);
+
+        let unaligned = UmemConfig {
+            flags: UmemConfig::XDP_UMEM_UNALIGNED_CHUNK_FLAG,
+            ..UmemConfig::default()
+        };
+        unaligned.validate_area(4096 + 2048).unwrap();
+
+        // The configuration itself is checked first.
+        let invalid = UmemConfig {
+            fill_size: 0,
+            ..UmemConfig::default()
+        };
+        assert_eq!(area_field(&invalid, 4096), ConfigField::FillSize);
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn area_page_count() {
+        let config = UmemConfig::default();
+        let page = crate::PageSize::Base.bytes();
+        let len = (1usize << 32) * page;
+        assert_eq!(area_field(&config, len), ConfigField::Area);
+        config.validate_area(len - page).unwrap();
+    }
+
+    #[test]
+    fn socket_without_rings() {
+        let config = SocketConfig {
+            rx_size: None,
+            tx_size: None,
+            bind_flags: 0,
+        };
+        assert_eq!(socket_field(config), ConfigField::RxSize);
+    }
+
+    #[test]
+    fn bind_flags() {
+        let config = SocketConfig {
+            bind_flags: 1 << 8,
+            ..socket()
+        };
+        assert_eq!(socket_field(config), ConfigField::BindFlags);
+
+        let config = SocketConfig {
+            bind_flags: SocketConfig::XDP_BIND_COPY | SocketConfig::XDP_BIND_ZEROCOPY,
+            ..socket()
+        };
+        assert_eq!(socket_field(config), ConfigField::BindFlags);
+    }
+
+    #[test]
+    fn display() {
+        let err = ConfigError::new(ConfigField::Headroom, "is wrong");
+        assert_eq!(alloc::format!("{}", err), "invalid `headroom`: is wrong");
+    }
+}
@@ -0,0 +1,420 @@
+//! Tests of the ring logic against the in-memory simulation of the kernel.
+use core::mem::MaybeUninit;
+use core::num::NonZeroU32;
+
+use xdpilone::xdp::{XdpDesc, XdpStatisticsV2};
+use xdpilone::{ConfigField, FillManager, SetupError, Simulator, SocketConfig, UmemConfig};
+
+const FRAME: u64 = 1 << 12;
+
+fn simulator(size: u32) -> Simulator {
+    let umem = UmemConfig {
+        fill_size: size,
+        complete_size: size,
+        ..UmemConfig::default()
+    };
+
+    Simulator::new(&umem, &simulated_socket(size)).unwrap()
+}
+
+fn simulated_socket(size: u32) -> SocketConfig {
+    SocketConfig {
+        rx_size: NonZeroU32::new(size),
+        tx_size: NonZeroU32::new(size),
+        bind_flags: 0,
+    }
+}
+
+fn config_field(err: Option<SetupError>) -> Option<ConfigField> {
+    err?.config().map(|err| err.field())
+}
+
+fn desc(frame: u64) -> XdpDesc {
+    XdpDesc {
+        addr: frame * FRAME,
+        len: 60,
+        options: 0,
+    }
+}
+
+#[test]
+fn invalid_config() {
+    let socket = SocketConfig {
+        rx_size: None,
+        tx_size: NonZeroU32::new(8),
+        bind_flags: 0,
+    };
+
+    let err = Simulator::new(&UmemConfig::default(), &socket).err();
+    assert_eq!(config_field(err), Some(ConfigField::RxSize));
+
+    let mut umem = UmemConfig::default();
+    umem.headroom = umem.frame_size;
+    let err = Simulator::new(&umem, &simulated_socket(8)).err();
+    assert_eq!(config_field(err), Some(ConfigField::Headroom));
+}
+
+#[test]
+fn loopback() {
+    let mut sim = simulator(8);
+
+    let mut fill = sim.fill(4);
+    assert_eq!(fill.insert((0..4).map(|i| i * FRAME)), 4);
+    fill.commit();
+    drop(fill);
+
+    let mut tx = sim.transmit(3);
+    assert_eq!(tx.insert((4..7).map(desc)), 3);
+    tx.commit();
+    drop(tx);
+
+    assert_eq!(sim.pending_fill(), 4);
+    assert_eq!(sim.pending_tx(), 3);
+    assert_eq!(sim.loopback(), 3);
+    assert_eq!(sim.pending_fill(), 1);
+    assert_eq!(sim.pending_tx(), 0);
+
+    let mut rx = sim.receive(8);
+    let received: Vec<_> = rx.by_ref().map(|desc| desc.addr).collect();
+    assert_eq!(received, [0, FRAME, 2 * FRAME]);
+    rx.release();
+    drop(rx);
+
+    let mut comp = sim.complete(8);
+    let completed: Vec<_> = comp.by_ref().collect();
+    assert_eq!(completed, [4 * FRAME, 5 * FRAME, 6 * FRAME]);
+    comp.release();
+}
+
+#[test]
+fn partial_commit() {
+    let mut sim = simulator(8);
+
+    let mut tx = sim.transmit(4);
+    assert_eq!(tx.capacity(), 4);
+    assert!(tx.insert_once(desc(0)));
+    assert!(tx.insert_once(desc(1)));
+    tx.commit();
+    assert!(tx.insert_once(desc(2)));
+    assert!(tx.insert_once(desc(3)));
+    assert!(!tx.insert_once(desc(4)));
+    tx.commit();
+    drop(tx);
+
+    let sent: Vec<_> = core::iter::from_fn(|| sim.take_tx())
+        .map(|desc| desc.addr)
+        .collect();
+    assert_eq!(sent, [0, FRAME, 2 * FRAME, 3 * FRAME]);
+}
+
+#[test]
+fn partial_release() {
+    let mut sim = simulator(8);
+
+    for i in 0..4 {
+        assert!(sim.push_completion(i * FRAME));
+    }
+
+    let mut comp = sim.complete(4);
+    assert_eq!(comp.read(), Some(0));
+    assert_eq!(comp.read(), Some(FRAME));
+    comp.release();
+    assert_eq!(comp.read(), Some(2 * FRAME));
+    assert_eq!(comp.read(), Some(3 * FRAME));
+    assert_eq!(comp.read(), None);
+    comp.release();
+    drop(comp);
+
+    assert_eq!(sim.complete(4).read(), None);
+    // All entries were released, the ring is free again.
+    for i in 0..8 {
+        assert!(sim.push_completion(i * FRAME));
+    }
+    assert!(!sim.push_completion(0));
+}
+
+#[test]
+fn uncommitted_writes_are_dropped() {
+    let mut sim = simulator(8);
+
+    let mut fill = sim.fill(4);
+    assert_eq!(fill.insert((0..4).map(|i| i * FRAME)), 4);
+    drop(fill);
+    assert_eq!(sim.pending_fill(), 0);
+
+    let mut fill = sim.fill(8);
+    assert_eq!(fill.capacity(), 8);
+    assert!(fill.insert_once(7 * FRAME));
+    fill.commit();
+    drop(fill);
+
+    assert_eq!(sim.take_fill(), Some(7 * FRAME));
+    assert_eq!(sim.take_fill(), None);
+}
+
+#[test]
+fn wraparound_slices() {
+    let mut sim = simulator(8);
+    let (mut sent, mut completed) = (0, 0);
+
+    // Odd batch sizes, such that the entries wrap around the end of the ring at varying points.
+    for batch in [3, 5, 7, 2, 6, 1, 8, 3, 5, 7] {
+        let descs: Vec<_> = (sent..sent + batch).map(desc).collect();
+
+        let mut tx = sim.transmit(batch as u32);
+        let n = tx.insert_slice(&descs);
+        tx.commit();
+        drop(tx);
+        sent += u64::from(n);
+
+        while let Some(desc) = sim.take_tx() {
+            assert!(sim.push_completion(desc.addr));
+        }
+
+        let mut comp = sim.complete(8);
+        let mut addrs = [0; 8];
+        let n = comp.read_into(&mut addrs);
+        comp.release();
+        drop(comp);
+
+        for &addr in &addrs[..n as usize] {
+            assert_eq!(addr, completed * FRAME);
+            completed += 1;
+        }
+    }
+
+    assert_eq!(sent, completed);
+    assert!(sent > 8);
+}
+
+#[test]
+fn in_place_slices() {
+    let mut sim = simulator(8);
+    let mut next = 0;
+    let mut expected = 0;
+
+    for batch in [5, 6, 7, 3] {
+        let mut fill = sim.fill(batch);
+        let (head, tail) = fill.as_mut_slices();
+        let written = (head.len() + tail.len()) as u32;
+        for slot in head.iter_mut().chain(tail) {
+            *slot = MaybeUninit::new(next * FRAME);
+            next += 1;
+        }
+        // Safety: all remaining slots were just initialized.
+        unsafe { fill.mark_written(written) };
+        fill.commit();
+        drop(fill);
+
+        while let Some(addr) = sim.take_fill() {
+            assert!(sim.push_rx(XdpDesc {
+                addr,
+                len: 60,
+                options: 0
+            }));
+        }
+
+        let mut rx = sim.receive(8);
+        let (head, tail) = rx.as_slices();
+        let available = (head.len() + tail.len()) as u32;
+        for desc in head.iter().chain(tail) {
+            assert_eq!(desc.addr, expected * FRAME);
+            expected += 1;
+        }
+        assert_eq!(rx.mark_read(available), available);
+        assert!(rx.read().is_none());
+        rx.release();
+    }
+
+    assert_eq!(next, expected);
+}
+
+#[test]
+fn multi_buffer_packets() {
+    let mut sim = simulator(8);
+
+    let frags = [desc(0), desc(1), desc(2)];
+    let mut tx = sim.transmit(8);
+    assert!(tx.insert_packet(&frags));
+    assert!(tx.insert_packet(&[desc(3)]));
+    assert!(!tx.insert_packet(&[desc(4); 5]));
+    tx.commit();
+    drop(tx);
+
+    while let Some(desc) = sim.take_tx() {
+        assert!(sim.push_rx(desc));
+    }
+
+    let mut rx = sim.receive(8);
+    let packet = rx.read_packet().unwrap();
+    assert_eq!(packet.len(), 3);
+    assert_eq!(packet.total_len(), 180);
+    let options: Vec<_> = packet.map(|desc| desc.options).collect();
+    assert_eq!(options, [XdpDesc::XDP_PKT_CONTD, XdpDesc::XDP_PKT_CONTD, 0]);
+
+    let packet = rx.read_packet().unwrap();
+    assert_eq!(packet.len(), 1);
+    assert!(rx.read_packet().is_none());
+}
+
+#[test]
+fn shared_transmit() {
+    const THREADS: u64 = 4;
+    const PER_THREAD: u64 = 16;
+    let mut sim = simulator(64);
+
+    let shared = sim.share_tx();
+    std::thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let shared = &shared;
+            scope.spawn(move || {
+                let first = thread * PER_THREAD;
+                let descs: Vec<_> = (first..first + PER_THREAD).map(desc).collect();
+                // One packet at a time, interleaving with the other threads.
+                for desc in descs.chunks(3) {
+                    assert_eq!(shared.insert_slice(desc), desc.len() as u32);
+                }
+            });
+        }
+    });
+    assert_eq!(shared.pending(), 64);
+    assert!(!shared.insert_once(desc(0)));
+    drop(shared);
+
+    let mut sent: Vec<_> = core::iter::from_fn(|| sim.take_tx())
+        .map(|desc| desc.addr / FRAME)
+        .collect();
+    assert_eq!(sent.len(), 64);
+    // The descriptors of each thread are published in the order it inserted them.
+    for thread in 0..THREADS {
+        let own: Vec<_> = sent.iter().filter(|&&i| i / PER_THREAD == thread).collect();
+        assert!(own.windows(2).all(|w| w[0] < w[1]));
+    }
+    sent.sort_unstable();
+    assert!(sent.iter().copied().eq(0..64));
+
+    // The exclusive handle continues where the shared one left off.
+    let mut tx = sim.transmit(64);
+    assert_eq!(tx.capacity(), 64);
+    assert!(tx.insert_once(desc(0)));
+    tx.commit();
+    drop(tx);
+    assert_eq!(sim.take_tx().map(|desc| desc.addr), Some(0));
+}
+
+#[test]
+fn fill_watermarks() {
+    let mut sim = simulator(16);
+    let manager = FillManager::new(4, 12).unwrap();
+    let mut frames = (0..).map(|i| i * FRAME);
+
+    assert_eq!(sim.refill(&manager, |w| w.insert(frames.by_ref())), 12);
+    assert_eq!(sim.pending_fill(), 12);
+    // Above the low watermark, the source is not asked for frames.
+    assert_eq!(sim.refill(&manager, |_| unreachable!()), 0);
+
+    for _ in 0..8 {
+        assert!(sim.take_fill().is_some());
+    }
+    assert_eq!(sim.refill(&manager, |w| w.insert(frames.by_ref())), 8);
+    assert_eq!(sim.pending_fill(), 12);
+    // Only the frames that fit were drawn from the source.
+    assert_eq!(frames.next(), Some(20 * FRAME));
+
+    // A source that runs dry commits what it has.
+    for _ in 0..12 {
+        assert!(sim.take_fill().is_some());
+    }
+    assert_eq!(sim.refill(&manager, |w| w.insert(0..3)), 3);
+    assert_eq!(sim.pending_fill(), 3);
+}
+
+#[test]
+fn fill_starvation() {
+    assert!(FillManager::new(8, 4).is_err());
+    assert!(FillManager::new(0, 0).is_err());
+
+    let mut manager = FillManager::new(0, 8).unwrap();
+    let mut stats = XdpStatisticsV2::default();
+    stats.rx_fill_ring_empty_descs = 5;
+    assert_eq!(manager.observe(&stats), 0);
+    assert_eq!(manager.observe(&stats), 0);
+    stats.rx_fill_ring_empty_descs = 9;
+    assert_eq!(manager.observe(&stats), 4);
+}
+
+#[test]
+fn insert_takes_only_what_fits() {
+    let mut sim = simulator(4);
+
+    let mut addrs = (0..8).map(|i| i * FRAME);
+    let mut fill = sim.fill(4);
+    assert_eq!(fill.insert(addrs.by_ref()), 4);
+    fill.commit();
+    drop(fill);
+    assert_eq!(addrs.next(), Some(4 * FRAME));
+
+    let mut descs = (0..8).map(desc);
+    let mut tx = sim.transmit(4);
+    assert_eq!(tx.insert(descs.by_ref()), 4);
+    tx.commit();
+    drop(tx);
+    assert_eq!(descs.next().map(|desc| desc.addr), Some(4 * FRAME));
+}
+
+#[test]
+fn recycle_completions() {
+    let mut sim = simulator(8);
+
+    // Some fill slots are taken, and the completions wrap around the end of their ring.
+    let mut fill = sim.fill(5);
+    assert_eq!(fill.insert((100..105).map(|i| i * FRAME)), 5);
+    fill.commit();
+    drop(fill);
+    for i in 0..6 {
+        assert!(sim.push_completion(i * FRAME));
+    }
+    let mut comp = sim.complete(6);
+    assert_eq!(comp.by_ref().count(), 6);
+    comp.release();
+    drop(comp);
+    for i in 0..7 {
+        assert!(sim.push_completion(i * FRAME));
+    }
+
+    // Only three fill slots are free.
+    assert_eq!(sim.recycle(8), 3);
+    assert_eq!(sim.recycle(8), 0);
+    // Free two more, but only recycle one.
+    assert!(sim.take_fill().is_some());
+    assert!(sim.take_fill().is_some());
+    assert_eq!(sim.recycle(1), 1);
+
+    let filled: Vec<_> = core::iter::from_fn(|| sim.take_fill()).collect();
+    let expected: Vec<_> = (102..105).chain(0..4).map(|i| i * FRAME).collect();
+    assert_eq!(filled, expected);
+
+    // The rest of the completions stay in order.
+    let completed: Vec<_> = sim.complete(8).collect();
+    assert_eq!(completed, [4 * FRAME, 5 * FRAME, 6 * FRAME]);
+}
+
+#[test]
+#[cfg(feature = "ring-invariants")]
+#[should_panic = "Ring invariant violated"]
+fn producer_overrun_is_detected() {
+    let mut sim = simulator(8);
+    // One more entry than the ring can hold.
+    sim.set_rx_producer(9);
+    let _ = sim.receive(8);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic = "the source of the refill reported a different number of frames"]
+fn refill_miscounted() {
+    let mut sim = simulator(16);
+    let manager = FillManager::new(4, 8).unwrap();
+    // The source inserts three frames but claims to have inserted four.
+    sim.refill(&manager, |w| w.insert(0..3) + 1);
+}